@@ -38,6 +38,10 @@ fn main() {
     // Tell cargo to tell rustc to link the solvext library.
     println!("cargo:rustc-link-lib=solvext");
 
+    // reading the rpm database, including the ndb and sqlite backends, is done through librpm
+    println!("cargo:rustc-link-lib=rpm");
+    println!("cargo:rustc-link-lib=rpmio");
+
     // Tell cargo to invalidate the built crate whenever the wrapper changes
     println!("cargo:rerun-if-changed=wrapper.h");
 
@@ -51,11 +55,16 @@ fn main() {
         .arg("-DENABLE_COMPS=1")
         .arg("-DENABLE_STATIC=1")
         .arg("-DDISABLE_SHARED=1")
+        .arg("-DENABLE_RPMMD=1")
+        .arg("-DENABLE_RPMPKG=1")
+        .arg("-DENABLE_RPMDB=1")
+        .arg("-DENABLE_RPMDB_LIBRPM=1")
         .arg("-DENABLE_SUSEREPO=1")
         .arg("-DENABLE_HELIXREPO=1")
         .arg("-DSUSE=1")
         .arg("-DENABLE_COMPLEX_DEPS=1")
         .arg("-DUSE_VENDORDIRS=1")
+        .arg("-DENABLE_LZMA_COMPRESSION=1")
         .arg("-DENABLE_BZIP2_COMPRESSION=1")
         .arg("-DENABLE_ZSTD_COMPRESSION=1")
         .arg("-DENABLE_ZCHUNK_COMPRESSION=1")
@@ -3,11 +3,20 @@
 #include <evr.h>
 #include <selection.h>
 #include <repo.h>
+#include <repo_solv.h>
+#include <repo_write.h>
+#include <solv_xfopen.h>
 #include <solver.h>
 #include <solverdebug.h>
 #include <transaction.h>
 #include <testcase.h>
 #include <repo_autopattern.h>
+#include <repo_rpmmd.h>
+#include <repo_repomdxml.h>
+#include <repo_updateinfoxml.h>
+#include <repo_content.h>
+#include <repo_susetags.h>
+#include <repo_rpmdb.h>
 #if 0
 #include <repoinfo.h>
 #include <repoinfo_cache.h>
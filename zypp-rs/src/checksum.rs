@@ -0,0 +1,246 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CheckSumError {
+    #[error("The checksum type {0} is not known")]
+    UnknownType(String),
+    #[error("{value} is not a valid {kind} checksum")]
+    InvalidValue {
+        kind: CheckSumType,
+        value: String
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckSumType {
+    Md5,
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl CheckSumType {
+    /// Length of the hex encoded checksum
+    pub fn hex_len( &self ) -> usize {
+        match self {
+            CheckSumType::Md5 => 32,
+            CheckSumType::Sha1 => 40,
+            CheckSumType::Sha224 => 56,
+            CheckSumType::Sha256 => 64,
+            CheckSumType::Sha384 => 96,
+            CheckSumType::Sha512 => 128,
+        }
+    }
+}
+
+impl FromStr for CheckSumType {
+    type Err = CheckSumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "md5" => Ok(CheckSumType::Md5),
+            // rpm-md uses "sha" for sha1
+            "sha1" | "sha" => Ok(CheckSumType::Sha1),
+            "sha224" => Ok(CheckSumType::Sha224),
+            "sha256" => Ok(CheckSumType::Sha256),
+            "sha384" => Ok(CheckSumType::Sha384),
+            "sha512" => Ok(CheckSumType::Sha512),
+            &_ => Err(CheckSumError::UnknownType(s.to_owned()))
+        }
+    }
+}
+
+impl fmt::Display for CheckSumType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CheckSumType::Md5 => "md5",
+            CheckSumType::Sha1 => "sha1",
+            CheckSumType::Sha224 => "sha224",
+            CheckSumType::Sha256 => "sha256",
+            CheckSumType::Sha384 => "sha384",
+            CheckSumType::Sha512 => "sha512",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A checksum of a file, e.g. as found in repomd.xml
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckSum {
+    kind: CheckSumType,
+    /// lowercase hex encoded value
+    value: String
+}
+
+impl CheckSum {
+    pub fn new( kind: CheckSumType, value: &str ) -> Result<Self, CheckSumError> {
+        let value = value.trim().to_lowercase();
+        if value.len() != kind.hex_len() || !value.chars().all(|c| c.is_ascii_hexdigit() ) {
+            return Err( CheckSumError::InvalidValue { kind, value } );
+        }
+        Ok( Self { kind, value } )
+    }
+
+    /// Creates a checksum from the type name and value, like they are used in repo metadata
+    pub fn from_type_str( kind: &str, value: &str ) -> Result<Self, CheckSumError> {
+        Self::new( CheckSumType::from_str(kind)?, value )
+    }
+
+    pub fn kind( &self ) -> CheckSumType {
+        self.kind
+    }
+
+    pub fn value( &self ) -> &str {
+        &self.value
+    }
+
+    /// Returns a hasher that calculates a checksum of the same type
+    pub fn hasher( &self ) -> Hasher {
+        Hasher::new( self.kind )
+    }
+
+    /// Calculates the checksum of the given file
+    pub fn of_file<P: AsRef<Path>>( kind: CheckSumType, file_path: P ) -> io::Result<Self> {
+        let mut file = File::open( file_path )?;
+        let mut hasher = Hasher::new( kind );
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let read = file.read( &mut buf )?;
+            if read == 0 {
+                break;
+            }
+            hasher.update( &buf[..read] );
+        }
+        Ok( hasher.finalize() )
+    }
+
+    /// Checks if the file at the given path matches this checksum
+    pub fn matches_file<P: AsRef<Path>>( &self, file_path: P ) -> io::Result<bool> {
+        Ok( Self::of_file( self.kind, file_path )? == *self )
+    }
+}
+
+impl fmt::Display for CheckSum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!( f, "{}:{}", self.kind, self.value )
+    }
+}
+
+/// Calculates a checksum incrementally, e.g. while a file is downloaded
+pub enum Hasher {
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha224(Sha224),
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    pub fn new( kind: CheckSumType ) -> Self {
+        match kind {
+            CheckSumType::Md5 => Hasher::Md5(Md5::new()),
+            CheckSumType::Sha1 => Hasher::Sha1(Sha1::new()),
+            CheckSumType::Sha224 => Hasher::Sha224(Sha224::new()),
+            CheckSumType::Sha256 => Hasher::Sha256(Sha256::new()),
+            CheckSumType::Sha384 => Hasher::Sha384(Sha384::new()),
+            CheckSumType::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+
+    pub fn update( &mut self, data: &[u8] ) {
+        match self {
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Sha224(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha384(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+        }
+    }
+
+    pub fn finalize( self ) -> CheckSum {
+        let (kind, value) = match self {
+            Hasher::Md5(h) => (CheckSumType::Md5, hex::encode(h.finalize())),
+            Hasher::Sha1(h) => (CheckSumType::Sha1, hex::encode(h.finalize())),
+            Hasher::Sha224(h) => (CheckSumType::Sha224, hex::encode(h.finalize())),
+            Hasher::Sha256(h) => (CheckSumType::Sha256, hex::encode(h.finalize())),
+            Hasher::Sha384(h) => (CheckSumType::Sha384, hex::encode(h.finalize())),
+            Hasher::Sha512(h) => (CheckSumType::Sha512, hex::encode(h.finalize())),
+        };
+        CheckSum { kind, value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // digests of "abc"
+    const ABC: [(CheckSumType, &str); 6] = [
+        (CheckSumType::Md5, "900150983cd24fb0d6963f7d28e17f72"),
+        (CheckSumType::Sha1, "a9993e364706816aba3e25717850c26c9cd0d89d"),
+        (CheckSumType::Sha224, "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7"),
+        (CheckSumType::Sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        (CheckSumType::Sha384, "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7"),
+        (CheckSumType::Sha512, "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"),
+    ];
+
+    #[test]
+    fn hasher_calculates_known_digests() {
+        for (kind, expected) in ABC {
+            let mut hasher = Hasher::new( kind );
+            hasher.update( b"a" );
+            hasher.update( b"bc" );
+            assert_eq!( hasher.finalize(), CheckSum::new( kind, expected ).unwrap(), "{}", kind );
+        }
+    }
+
+    #[test]
+    fn checksum_of_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join( "abc" );
+        std::fs::write( &path, b"abc" ).unwrap();
+
+        for (kind, expected) in ABC {
+            let sum = CheckSum::of_file( kind, &path ).unwrap();
+            assert_eq!( sum.kind(), kind );
+            assert_eq!( sum.value(), expected );
+            assert!( CheckSum::new( kind, expected ).unwrap().matches_file( &path ).unwrap() );
+        }
+        assert!( CheckSum::of_file( CheckSumType::Sha256, dir.path().join( "missing" ) ).is_err() );
+    }
+
+    #[test]
+    fn type_names() {
+        assert_eq!( CheckSumType::from_str( "sha" ).unwrap(), CheckSumType::Sha1 );
+        assert_eq!( CheckSumType::from_str( "SHA256" ).unwrap(), CheckSumType::Sha256 );
+        assert!( matches!( CheckSumType::from_str( "crc32" ), Err(CheckSumError::UnknownType(_)) ) );
+
+        let sum = CheckSum::from_type_str( "sha", ABC[1].1 ).unwrap();
+        assert_eq!( sum.to_string(), format!( "sha1:{}", ABC[1].1 ) );
+    }
+
+    #[test]
+    fn values_are_validated() {
+        let sum = CheckSum::new( CheckSumType::Md5, " 900150983CD24FB0D6963F7D28E17F72\n" ).unwrap();
+        assert_eq!( sum.value(), ABC[0].1 );
+
+        // a sha1 value is too short for sha256 and too long for md5
+        assert!( matches!( CheckSum::new( CheckSumType::Sha256, ABC[1].1 ), Err(CheckSumError::InvalidValue{ .. }) ) );
+        assert!( matches!( CheckSum::new( CheckSumType::Md5, ABC[1].1 ), Err(CheckSumError::InvalidValue{ .. }) ) );
+        assert!( CheckSum::new( CheckSumType::Md5, "z00150983cd24fb0d6963f7d28e17f72" ).is_err() );
+        assert!( CheckSum::new( CheckSumType::Md5, "" ).is_err() );
+    }
+}
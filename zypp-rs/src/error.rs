@@ -3,7 +3,11 @@ use std::io;
 
 use thiserror::Error;
 use crate::repoinfo::Error as RepoInfoError;
+use crate::serviceinfo::Error as ServiceInfoError;
 use crate::media::MediaError as MediaError;
+use crate::repomanager::RepoManagerError;
+use crate::repomd::RepoMdError;
+use crate::susetags::SusetagsError;
 
 #[derive(Error, Debug)]
 pub enum ZyppError {
@@ -24,6 +28,30 @@ pub enum ZyppError {
         #[from]
         source: RepoInfoError
     },
+    #[error("Service Error - {source}")]
+    Service {
+        #[from]
+        source: ServiceInfoError
+    },
+
+    #[error("Repo Manager Error - {source}")]
+    RepoManager {
+        #[from]
+        source: RepoManagerError
+    },
+
+    #[error("Repo Metadata Error - {source}")]
+    RepoMd {
+        #[from]
+        source: RepoMdError
+    },
+
+    #[error("Susetags Metadata Error - {source}")]
+    Susetags {
+        #[from]
+        source: SusetagsError
+    },
+
     #[error("IO Error - {source}")]
     IoError{
         #[from]
@@ -48,7 +76,11 @@ pub struct UnknownPackage {
 #[derive(Error, Debug)]
 pub enum PoolError {
     #[error(transparent)]
-    UnknownPackage(#[from] UnknownPackage)
+    UnknownPackage(#[from] UnknownPackage),
+    #[error("libsolv error - {0}")]
+    Libsolv(String),
+    #[error("IO Error - {0}")]
+    Io(#[from] io::Error)
 }
 
 #[derive(Error, Debug)]
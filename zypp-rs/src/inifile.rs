@@ -0,0 +1,291 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// Indent libzypp uses for continuation lines of multi value keys
+const CONTINUATION_INDENT: &str = "        ";
+
+#[derive(Debug, Clone)]
+enum Line {
+  /// comments, empty lines and everything else we do not need to understand,
+  /// those are written back exactly like they were read
+  Other(String),
+  Section {
+    name: String,
+    raw: String
+  },
+  /// a key with its value, including all continuation lines as they appeared in the file
+  Entry {
+    key: String,
+    raw: Vec<String>
+  }
+}
+
+/// Minimal ini document used to rewrite .repo and .service files.
+///
+/// Contrary to configparser this keeps comments, the order of keys and keys we do not know
+/// about, so a file that was edited by hand only changes where we actually changed a value.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IniFile {
+  lines: Vec<Line>
+}
+
+fn is_comment( line: &str ) -> bool {
+  let trimmed = line.trim_start();
+  trimmed.starts_with('#') || trimmed.starts_with(';')
+}
+
+impl IniFile {
+
+  pub fn parse( content: &str ) -> Self {
+    let mut lines: Vec<Line> = Vec::new();
+    for line in content.lines() {
+      let trimmed = line.trim();
+
+      // indented lines directly following a key are continuation lines of that key
+      if line.starts_with(char::is_whitespace) && !trimmed.is_empty() && !is_comment(line) {
+        if let Some(Line::Entry{ raw, .. }) = lines.last_mut() {
+          raw.push( line.to_owned() );
+          continue;
+        }
+      }
+
+      if trimmed.is_empty() || is_comment(line) {
+        lines.push( Line::Other(line.to_owned()) );
+      } else if trimmed.starts_with('[') && trimmed.ends_with(']') {
+        lines.push( Line::Section { name: trimmed[1..trimmed.len()-1].trim().to_owned(), raw: line.to_owned() } );
+      } else if let Some((key, _)) = trimmed.split_once('=') {
+        lines.push( Line::Entry { key: key.trim().to_owned(), raw: vec![line.to_owned()] } );
+      } else {
+        lines.push( Line::Other(line.to_owned()) );
+      }
+    }
+    Self { lines }
+  }
+
+  pub fn load<P: AsRef<Path>>( file_path: P ) -> io::Result<Self> {
+    Ok( Self::parse( &fs::read_to_string(file_path)? ) )
+  }
+
+  /// Writes the document into a temporary file next to the target and moves it
+  /// in place, so readers never see a half written file.
+  pub fn save<P: AsRef<Path>>( &self, file_path: P ) -> io::Result<()> {
+    let dir = file_path.as_ref().parent().filter(|p| !p.as_os_str().is_empty() ).unwrap_or( Path::new(".") );
+    let mut tmp_file = NamedTempFile::new_in( dir )?;
+    tmp_file.write_all( self.to_string().as_bytes() )?;
+    tmp_file.as_file().sync_all()?;
+    tmp_file.persist( file_path ).map_err(|e| e.error )?;
+    Ok(())
+  }
+
+  pub fn sections( &self ) -> Vec<&str> {
+    self.lines.iter().filter_map( |l| match l {
+      Line::Section { name, .. } => Some(name.as_str()),
+      _ => None
+    }).collect()
+  }
+
+  pub fn has_section( &self, section: &str ) -> bool {
+    self.section_range(section).is_some()
+  }
+
+  /// Returns the index of the section header and the index of the first line not belonging to it anymore
+  fn section_range( &self, section: &str ) -> Option<(usize, usize)> {
+    let start = self.lines.iter().position( |l| matches!( l, Line::Section{ name, .. } if name == section ) )?;
+    let end = self.lines[start+1..].iter()
+      .position( |l| matches!( l, Line::Section{ .. } ) )
+      .map_or( self.lines.len(), |p| p + start + 1 );
+    Some((start, end))
+  }
+
+  fn find_key( &self, section: &str, key: &str ) -> Option<usize> {
+    let (start, end) = self.section_range(section)?;
+    (start+1..end).find( |i| matches!( &self.lines[*i], Line::Entry{ key: k, .. } if k.eq_ignore_ascii_case(key) ) )
+  }
+
+  pub fn has_key( &self, section: &str, key: &str ) -> bool {
+    self.find_key( section, key ).is_some()
+  }
+
+  /// Returns all values of a key, one per line, with surrounding whitespace removed
+  pub fn get( &self, section: &str, key: &str ) -> Option<Vec<String>> {
+    let idx = self.find_key( section, key )?;
+    match &self.lines[idx] {
+      Line::Entry{ raw, .. } => Some( raw.iter().enumerate()
+        .map( |(i, l)| if i == 0 { l.split_once('=').map_or( "", |(_, v)| v ) } else { l.as_str() } )
+        .map( |v| v.trim().to_owned() )
+        .collect() ),
+      _ => None
+    }
+  }
+
+  /// Sets the values for a key, the first value is written on the key line itself, all others
+  /// as indented continuation lines. If the key exists already it is replaced in place, otherwise
+  /// it is added after the last key of the section. Missing sections are appended to the document.
+  pub fn set( &mut self, section: &str, key: &str, values: &[String] ) {
+    // do not touch the formatting of values that did not change
+    if self.get( section, key ).map_or( false, |old| old == values ) {
+      return;
+    }
+
+    let mut raw = Vec::with_capacity(values.len());
+    for (i, v) in values.iter().enumerate() {
+      if i == 0 {
+        raw.push( format!("{}={}", key, v) );
+      } else {
+        raw.push( format!("{}{}", CONTINUATION_INDENT, v) );
+      }
+    }
+    if raw.is_empty() {
+      raw.push( format!("{}=", key) );
+    }
+
+    if let Some(idx) = self.find_key( section, key ) {
+      if let Line::Entry{ raw: old_raw, .. } = &mut self.lines[idx] {
+        *old_raw = raw;
+      }
+      return;
+    }
+
+    let entry = Line::Entry { key: key.to_owned(), raw };
+    match self.section_range( section ) {
+      Some((start, end)) => {
+        let insert_at = (start+1..end).rev()
+          .find( |i| matches!( self.lines[*i], Line::Entry{ .. } ) )
+          .map_or( start + 1, |i| i + 1 );
+        self.lines.insert( insert_at, entry );
+      },
+      None => {
+        let needs_separator = self.lines.last().map_or( false, |l| !matches!( l, Line::Other(s) if s.trim().is_empty() ) );
+        if needs_separator {
+          self.lines.push( Line::Other(String::new()) );
+        }
+        self.lines.push( Line::Section { name: section.to_owned(), raw: format!("[{}]", section) } );
+        self.lines.push( entry );
+      }
+    }
+  }
+
+  pub fn remove_key( &mut self, section: &str, key: &str ) {
+    if let Some(idx) = self.find_key( section, key ) {
+      self.lines.remove(idx);
+    }
+  }
+
+  /// Renames a section, its keys and comments stay where they are
+  pub fn rename_section( &mut self, section: &str, new_name: &str ) {
+    if let Some((start, _)) = self.section_range( section ) {
+      if let Line::Section{ name, raw } = &mut self.lines[start] {
+        *raw = raw.replacen( section, new_name, 1 );
+        *name = new_name.to_owned();
+      }
+    }
+  }
+
+  /// Removes the section header and all lines up to the next section
+  pub fn remove_section( &mut self, section: &str ) {
+    if let Some((start, end)) = self.section_range( section ) {
+      self.lines.drain( start..end );
+    }
+  }
+}
+
+impl fmt::Display for IniFile {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for line in &self.lines {
+      match line {
+        Line::Other(raw) | Line::Section{ raw, .. } => writeln!( f, "{}", raw )?,
+        Line::Entry{ raw, .. } => {
+          for l in raw {
+            writeln!( f, "{}", l )?;
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const REPO_FILE: &str = "\
+# managed by hand
+[oss]
+name=Main Repository
+enabled = 1
+# the mirror is faster
+baseurl=http://mirror/oss/
+        http://download/oss/
+   ; indented comment
+autorefresh=1
+
+[update]
+name=Update Repository
+baseurl=http://download/update/
+";
+
+  fn values( v: &[&str] ) -> Vec<String> {
+    v.iter().map( |s| s.to_string() ).collect()
+  }
+
+  #[test]
+  fn parse_and_save_keeps_the_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("test.repo");
+    IniFile::parse( REPO_FILE ).save( &path ).unwrap();
+    assert_eq!( fs::read_to_string( &path ).unwrap(), REPO_FILE );
+
+    let ini = IniFile::load( &path ).unwrap();
+    assert_eq!( ini.sections(), vec!["oss", "update"] );
+    assert_eq!( ini.get( "oss", "enabled" ), Some( values( &["1"] ) ) );
+    assert_eq!( ini.get( "oss", "BaseURL" ), Some( values( &["http://mirror/oss/", "http://download/oss/"] ) ) );
+    assert_eq!( ini.get( "oss", "missing" ), None );
+  }
+
+  #[test]
+  fn set_replaces_a_key_in_place() {
+    let mut ini = IniFile::parse( REPO_FILE );
+    ini.set( "oss", "baseurl", &values( &["http://other/oss/"] ) );
+    assert_eq!( ini.to_string(), REPO_FILE.replace( "baseurl=http://mirror/oss/\n        http://download/oss/\n", "baseurl=http://other/oss/\n" ) );
+
+    // unchanged values keep their formatting
+    ini.set( "oss", "enabled", &values( &["1"] ) );
+    assert!( ini.to_string().contains( "enabled = 1\n" ) );
+
+    ini.set( "oss", "gpgcheck", &values( &["0"] ) );
+    assert!( ini.to_string().contains( "autorefresh=1\ngpgcheck=0\n\n[update]" ) );
+    ini.set( "new", "name", &values( &["New"] ) );
+    assert!( ini.to_string().ends_with( "baseurl=http://download/update/\n\n[new]\nname=New\n" ) );
+  }
+
+  #[test]
+  fn other_sections_stay_untouched() {
+    let (oss, update) = REPO_FILE.split_at( REPO_FILE.find( "[update]" ).unwrap() );
+
+    let mut ini = IniFile::parse( REPO_FILE );
+    ini.rename_section( "update", "updates" );
+    assert_eq!( ini.sections(), vec!["oss", "updates"] );
+    assert_eq!( ini.to_string(), format!( "{}{}", oss, update.replace( "[update]", "[updates]" ) ) );
+
+    let mut ini = IniFile::parse( REPO_FILE );
+    ini.remove_section( "oss" );
+    assert_eq!( ini.to_string(), format!( "# managed by hand\n{}", update ) );
+
+    let mut ini = IniFile::parse( REPO_FILE );
+    ini.remove_section( "update" );
+    assert_eq!( ini.to_string(), oss );
+  }
+
+  #[test]
+  fn remove_key_removes_all_continuation_lines() {
+    let mut ini = IniFile::parse( REPO_FILE );
+    ini.remove_key( "oss", "baseurl" );
+    assert!( !ini.has_key( "oss", "baseurl" ) );
+    assert!( ini.has_key( "update", "baseurl" ) );
+    assert_eq!( ini.to_string(), REPO_FILE.replace( "baseurl=http://mirror/oss/\n        http://download/oss/\n", "" ) );
+  }
+}
@@ -1,5 +1,12 @@
 pub mod solv;
 pub mod repoinfo;
+pub mod repovariables;
+pub mod serviceinfo;
 pub mod error;
 pub mod repomanager;
 pub mod media;
+pub mod checksum;
+pub mod repomd;
+pub mod susetags;
+pub mod plaindir;
+mod inifile;
@@ -3,14 +3,38 @@ use std::path::PathBuf;
 use url::Url;
 
 use crate::error::ZyppError;
-use crate::media::spec::{MediaSpec, FileSpec};
+use crate::media::MediaError;
+use crate::media::spec::{MediaSpec, FileSpec, ProvideStatus};
 
 #[async_trait]
 pub trait MediaDriver : Send {
     fn schemes( &self ) -> Vec<String>;
 
     async fn attach( &self, urls: Vec<Url>, spec: MediaSpec ) -> Result<u32, ZyppError>;
-    async fn provide( &self, attachId: u32, path: PathBuf, spec: FileSpec ) -> Result<PathBuf, ZyppError>;
+    /// Provides a file of the medium locally, or only checks that it exists if the spec has
+    /// checkExistsOnly set. Files that are not there are a MediaError::FileNotFound, the manager
+    /// turns it into ProvideStatus::NotFound for optional files.
+    async fn provide( &self, attachId: u32, path: PathBuf, spec: FileSpec ) -> Result<ProvideStatus, ZyppError>;
+
+    /// Lists the entries of a directory on the medium, names of subdirectories end with a '/'.
+    /// Drivers that can not list directories keep this default implementation.
+    async fn list_dir( &self, _attachId: u32, _path: PathBuf ) -> Result<Vec<String>, ZyppError> {
+        Err( MediaError::NotSupported.into() )
+    }
 
     fn detach( &self, id: u32 ) -> Result<(), ZyppError>;
 }
+
+/// Picks the error to report when all mirrors of a medium failed: a broken file beats a
+/// failed request, which beats a file that is simply not there. On a tie the newer one wins.
+pub(crate) fn worse_error( kept: Option<ZyppError>, new: ZyppError ) -> Option<ZyppError> {
+    let severity = |e: &ZyppError| match e {
+        ZyppError::Media { source: MediaError::FileNotFound } => 0,
+        ZyppError::Media { source: MediaError::ChecksumMismatch { .. } | MediaError::DownloadSizeExceeded(_) } => 2,
+        _ => 1
+    };
+    match kept {
+        Some(kept) if severity( &kept ) > severity( &new ) => Some( kept ),
+        _ => Some( new )
+    }
+}
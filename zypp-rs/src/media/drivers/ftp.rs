@@ -0,0 +1,616 @@
+use async_trait::async_trait;
+use log::info;
+use percent_encoding::percent_decode_str;
+use tempfile::{TempDir, NamedTempFile};
+use tokio::fs::{DirBuilder, File};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_native_tls::{native_tls, TlsConnector};
+use tribool::Tribool::{True,False,Indeterminate};
+use url::Url;
+use scopeguard::defer;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::checksum::Hasher;
+use crate::error::ZyppError;
+use crate::media::{MediaError, driver::{MediaDriver, worse_error}, spec::{*}};
+use super::local::MediaLocalDriver;
+
+/// The control and data connections are either plain tcp or tls streams
+trait FtpStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> FtpStream for T {}
+
+struct FtpReply {
+    code: u32,
+    text: String
+}
+
+/// A minimal FTP client, it only knows passive mode and what is needed to download
+/// files and list directories.
+struct FtpConnection {
+    control: BufReader<Box<dyn FtpStream>>,
+    host: String,
+    /// data connections go to the server we are talking to, whatever address PASV reports
+    peer: IpAddr,
+    /// set once the control connection is protected, data connections are protected as well then
+    tls: Option<TlsConnector>
+}
+
+impl FtpConnection {
+
+    /// Connects and logs in, with the credentials in the url or anonymously.
+    /// ftps urls use explicit TLS, so the connection is upgraded with AUTH TLS on the ftp port.
+    async fn connect( url: &Url ) -> Result<Self, ZyppError> {
+        let host = url.host_str().ok_or( MediaError::InvalidUrl )?.to_owned();
+        let tcp = TcpStream::connect( ( host.as_str(), url.port().unwrap_or(21) ) ).await?;
+        let peer = tcp.peer_addr()?.ip();
+
+        let mut conn = Self { control: BufReader::new( Box::new(tcp) ), host, peer, tls: None };
+        conn.expect( "connect", &[220] ).await?;
+
+        if url.scheme() == "ftps" {
+            conn.command( "AUTH TLS", &[234] ).await?;
+            conn = conn.upgrade( Self::tls_connector( url )? ).await?;
+        }
+
+        let user = match url.username() {
+            "" => "anonymous".to_owned(),
+            user => percent_decode_str( user ).decode_utf8_lossy().to_string()
+        };
+        let password = url.password()
+            .map( |p| percent_decode_str( p ).decode_utf8_lossy().to_string() )
+            .unwrap_or( "anonymous@".to_owned() );
+        let reply = conn.command( &format!("USER {}", user), &[230, 331] ).await?;
+        if reply.code == 331 {
+            conn.send( &format!("PASS {}", password) ).await?;
+            conn.expect( "PASS", &[230, 202] ).await?;
+        }
+
+        if conn.tls.is_some() {
+            conn.command( "PBSZ 0", &[200] ).await?;
+            conn.command( "PROT P", &[200] ).await?;
+        }
+        conn.command( "TYPE I", &[200] ).await?;
+        Ok(conn)
+    }
+
+    /// Certificates are verified unless the url says ssl_verify=no, like in libzypp
+    fn tls_connector( url: &Url ) -> Result<TlsConnector, ZyppError> {
+        let verify = MediaLocalDriver::query_param( url, "ssl_verify" ).as_deref() != Some("no");
+        let connector = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs( !verify )
+            .build()
+            .map_err( |e| MediaError::FtpError( e.to_string() ) )?;
+        Ok( TlsConnector::from( connector ) )
+    }
+
+    async fn upgrade( self, connector: TlsConnector ) -> Result<Self, ZyppError> {
+        let stream = connector.connect( &self.host, self.control.into_inner() ).await
+            .map_err( |e| MediaError::FtpError( e.to_string() ) )?;
+        Ok( Self { control: BufReader::new( Box::new(stream) ), host: self.host, peer: self.peer, tls: Some(connector) } )
+    }
+
+    async fn send( &mut self, cmd: &str ) -> Result<(), ZyppError> {
+        let control = self.control.get_mut();
+        control.write_all( format!("{}\r\n", cmd).as_bytes() ).await?;
+        control.flush().await?;
+        Ok(())
+    }
+
+    /// Reads a reply, replies spanning multiple lines start with "123-" and end with "123 "
+    async fn reply( &mut self ) -> Result<FtpReply, ZyppError> {
+        let mut line = String::new();
+        if self.control.read_line( &mut line ).await? == 0 {
+            return Err( MediaError::FtpError( "The server closed the connection".to_owned() ).into() );
+        }
+        let code: u32 = line.get(0..3).and_then( |c| c.parse().ok() )
+            .ok_or_else( || MediaError::FtpError( format!("Invalid reply: {}", line.trim_end()) ) )?;
+        let mut text = line[3..].trim().to_owned();
+
+        if line.as_bytes().get(3) == Some(&b'-') {
+            let last = format!("{} ", code);
+            loop {
+                line.clear();
+                if self.control.read_line( &mut line ).await? == 0 {
+                    return Err( MediaError::FtpError( "The server closed the connection".to_owned() ).into() );
+                }
+                text.push('\n');
+                text.push_str( line.trim_end() );
+                if line.starts_with( &last ) {
+                    break;
+                }
+            }
+        }
+        Ok( FtpReply { code, text } )
+    }
+
+    async fn expect( &mut self, what: &str, codes: &[u32] ) -> Result<FtpReply, ZyppError> {
+        let reply = self.reply().await?;
+        if !codes.contains( &reply.code ) {
+            return Err( match reply.code {
+                550 => MediaError::FileNotFound,
+                530 => MediaError::FtpError( "Login failed".to_owned() ),
+                code => MediaError::FtpError( format!("{} failed: {} {}", what, code, reply.text) )
+            }.into() );
+        }
+        Ok(reply)
+    }
+
+    async fn command( &mut self, cmd: &str, codes: &[u32] ) -> Result<FtpReply, ZyppError> {
+        self.send( cmd ).await?;
+        // only the verb, so no password ever ends up in an error
+        let verb = cmd.split(' ').next().unwrap_or( cmd );
+        self.expect( verb, codes ).await
+    }
+
+    /// Asks for a passive data port, EPSV first and PASV for older servers
+    async fn passive_port( &mut self ) -> Result<u16, ZyppError> {
+        let invalid = |text: &str| MediaError::FtpError( format!("Invalid passive mode reply: {}", text) );
+        if let Ok(reply) = self.command( "EPSV", &[229] ).await {
+            // Entering Extended Passive Mode (|||6446|)
+            return reply.text.split("(|||").nth(1)
+                .and_then( |r| r.split('|').next() )
+                .and_then( |p| p.parse().ok() )
+                .ok_or_else( || invalid( &reply.text ).into() );
+        }
+
+        let reply = self.command( "PASV", &[227] ).await?;
+        parse_pasv( &reply.text ).ok_or_else( || invalid( &reply.text ).into() )
+    }
+
+    /// Sends a command that transfers data and returns the data connection
+    async fn transfer( &mut self, cmd: &str ) -> Result<Box<dyn FtpStream>, ZyppError> {
+        let port = self.passive_port().await?;
+        let tcp = TcpStream::connect( (self.peer, port) ).await?;
+        self.command( cmd, &[125, 150] ).await?;
+
+        // servers start the TLS handshake on the data connection after accepting the command.
+        // native-tls can not resume the session of the control connection for it, so servers
+        // insisting on that, like vsftpd with its default require_ssl_reuse=YES, refuse the transfer.
+        match &self.tls {
+            Some(connector) => {
+                let stream = connector.connect( &self.host, tcp ).await
+                    .map_err( |e| MediaError::FtpError( e.to_string() ) )?;
+                Ok( Box::new(stream) )
+            },
+            None => Ok( Box::new(tcp) )
+        }
+    }
+
+    async fn finish_transfer( &mut self, mut data: Box<dyn FtpStream> ) -> Result<(), ZyppError> {
+        data.shutdown().await?;
+        drop(data);
+        self.expect( "transfer", &[226, 250] ).await?;
+        Ok(())
+    }
+
+    /// Downloads a file into target, aborting once it gets bigger than the download size
+    /// of the spec. Returns the hasher for the checksum of the spec, fed with the whole file.
+    async fn retrieve( &mut self, path: &str, target: &mut File, spec: &FileSpec ) -> Result<Option<Hasher>, ZyppError> {
+        let mut data = self.transfer( &format!("RETR {}", path) ).await?;
+        let mut hasher = spec.checksum_hasher();
+        let mut downloaded: u64 = 0;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let read = data.read( &mut buf ).await?;
+            if read == 0 {
+                break;
+            }
+            downloaded += read as u64;
+            spec.check_download_size( downloaded )?;
+            if let Some(hasher) = hasher.as_mut() {
+                hasher.update( &buf[..read] );
+            }
+            target.write_all( &buf[..read] ).await?;
+        }
+        self.finish_transfer( data ).await?;
+        Ok(hasher)
+    }
+
+    /// Checks that a file exists without downloading it, SIZE fails with 550 for files
+    /// that are not there. The modification time is only known if the server has MDTM.
+    async fn stat( &mut self, path: &str ) -> Result<FileInfo, ZyppError> {
+        let size = self.command( &format!("SIZE {}", path), &[213] ).await?.text.parse().ok();
+        let mtime = match self.command( &format!("MDTM {}", path), &[213] ).await {
+            Ok(reply) => parse_mdtm( &reply.text ),
+            Err(_) => None
+        };
+        Ok( FileInfo { size, mtime } )
+    }
+
+    /// Lists a directory with MLSD, which tells files and directories apart, or with
+    /// NLST for servers that do not know it. NLST only gives names, so directories are
+    /// not marked with a trailing '/' then and a plaindir repo on such a server is only
+    /// read from its top directory instead of recursively.
+    async fn list( &mut self, path: &str ) -> Result<Vec<String>, ZyppError> {
+        let (mut data, machine_readable) = match self.transfer( &format!("MLSD {}", path) ).await {
+            Ok(data) => (data, true),
+            Err(ZyppError::Media { source: MediaError::FileNotFound }) => return Err( MediaError::FileNotFound.into() ),
+            Err(_) => ( self.transfer( &format!("NLST {}", path) ).await?, false )
+        };
+        let mut listing = String::new();
+        data.read_to_string( &mut listing ).await?;
+        self.finish_transfer( data ).await?;
+
+        let mut entries: Vec<String> = listing.lines().filter_map( |line| {
+            if !machine_readable {
+                let name = line.rsplit('/').next().unwrap_or( line ).trim();
+                return Some( name.to_owned() ).filter( |n| !n.is_empty() && n != "." && n != ".." );
+            }
+            // type=dir;modify=20231201120000; name
+            let (facts, name) = line.split_once(' ')?;
+            let kind = facts.split(';').find_map( |f| f.to_ascii_lowercase().strip_prefix("type=").map( str::to_owned ) )?;
+            match kind.as_str() {
+                "dir" => Some( format!("{}/", name) ),
+                "file" => Some( name.to_owned() ),
+                // the listed directory itself and its parent
+                _ => None
+            }
+        }).collect();
+        entries.sort();
+        Ok(entries)
+    }
+
+    async fn quit( mut self ) {
+        let _ = self.command( "QUIT", &[221] ).await;
+    }
+}
+
+/// Takes the port from a PASV reply like "Entering Passive Mode (h1,h2,h3,h4,p1,p2)".
+/// The host fields are checked but ignored on purpose, data connections always go to the
+/// peer of the control connection, so a server behind NAT reporting its private address
+/// works and a malicious one can not make us connect elsewhere.
+fn parse_pasv( text: &str ) -> Option<u16> {
+    let fields = text.split(['(', ')']).nth(1)?
+        .split(',')
+        .map( |x| x.trim().parse::<u8>().ok() )
+        .collect::<Option<Vec<u8>>>()?;
+    match fields.as_slice() {
+        [_, _, _, _, p1, p2] => Some( u16::from(*p1) << 8 | u16::from(*p2) ),
+        _ => None
+    }
+}
+
+/// Converts a MDTM timestamp like 20231201120000, which is in UTC, into seconds since the epoch
+fn parse_mdtm( text: &str ) -> Option<u64> {
+    let digits = text.get(0..14).filter( |d| d.bytes().all( |b| b.is_ascii_digit() ) )?;
+    let num = |from: usize, to: usize| digits[from..to].parse::<i64>().ok();
+    let (year, month, day) = ( num(0, 4)?, num(4, 6)?, num(6, 8)? );
+    let (hour, minute, second) = ( num(8, 10)?, num(10, 12)?, num(12, 14)? );
+
+    // days since the epoch, see Howard Hinnant's days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid( 400 );
+    let year_of_era = year - era * 400;
+    let day_of_year = ( 153 * ( ( month + 9 ) % 12 ) + 2 ) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    u64::try_from( days * 86400 + hour * 3600 + minute * 60 + second ).ok()
+}
+
+struct AttachedMedia {
+    use_cnt: u32,
+    attach_dir: TempDir,
+    mirrors: Vec<Url>,
+    spec: MediaSpec
+}
+
+struct MediaFtpDriverShared {
+    next_attach_id: u32,
+    attached_media: HashMap<u32, AttachedMedia>
+}
+
+/// Downloads files from ftp and ftps servers, see FtpConnection::connect for the logins.
+/// ftps data connections do not reuse the TLS session, see FtpConnection::transfer.
+pub struct MediaFtpDriver {
+    inner: Mutex<MediaFtpDriverShared>
+}
+
+impl MediaFtpDriver {
+
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new( MediaFtpDriverShared { next_attach_id: 1, attached_media: Default::default() } )
+        }
+    }
+
+    /// The path on the server, paths on the medium are relative to the mirror path
+    fn remote_path( mirror: &Url, path_on_medium: &Path ) -> Result<String, ZyppError> {
+        let base = percent_decode_str( mirror.path() ).decode_utf8_lossy().to_string();
+        let path = MediaLocalDriver::local_path( Path::new( &base ), path_on_medium )?;
+        Ok( path.to_str().ok_or( MediaError::InvalidPath )?.to_owned() )
+    }
+
+    /// Downloads the file into a temporary file next to the target, which is only renamed
+    /// to the target once the checksum from the spec matches
+    async fn download_file<P: AsRef<Path>>( mirror: &Url, path_on_medium: &Path, target_path: P, target_file_name: &str, spec: &FileSpec ) -> Result<PathBuf, ZyppError> {
+
+        let target_file_path = target_path.as_ref().join(target_file_name);
+        let tmp_file = NamedTempFile::new_in( &target_path )?.into_temp_path();
+
+        info!("Downloading into tmp path: {}", tmp_file.to_str().unwrap_or_default() );
+
+        let remote_path = Self::remote_path( mirror, path_on_medium )?;
+        let mut file = File::create( &tmp_file ).await?;
+
+        let mut conn = FtpConnection::connect( mirror ).await?;
+        let hasher = conn.retrieve( &remote_path, &mut file, spec ).await?;
+        conn.quit().await;
+
+        file.sync_all().await?;
+        // a broken file is never persisted, the temp file is removed when dropped
+        spec.verify_checksum( hasher )?;
+        tmp_file.persist( &target_file_path ).map_err(|e| e.error )?;
+        Ok(target_file_path)
+    }
+
+    async fn file_exists( mirror: &Url, path_on_medium: &Path ) -> Result<ProvideStatus, ZyppError> {
+        let remote_path = Self::remote_path( mirror, path_on_medium )?;
+        let mut conn = FtpConnection::connect( mirror ).await?;
+        let info = conn.stat( &remote_path ).await?;
+        conn.quit().await;
+        Ok( ProvideStatus::Exists(info) )
+    }
+
+    /// Marks the medium used while a request runs, release it with detach
+    fn use_medium( &self, attach_id: u32 ) -> Result<(PathBuf, Vec<Url>), ZyppError> {
+        let mut inner = self.inner.lock()?;
+        let medium = inner.attached_media.get_mut(&attach_id).ok_or( MediaError::InvalidHandle )?;
+        medium.use_cnt += 1;
+        Ok( (medium.attach_dir.path().to_owned(), medium.mirrors.clone()) )
+    }
+}
+
+#[async_trait]
+impl MediaDriver for MediaFtpDriver {
+    fn schemes( &self ) -> Vec<String> {
+        vec!["ftp".to_owned(), "ftps".to_owned()]
+    }
+
+    async fn attach( &self, urls: Vec<Url>, spec: MediaSpec ) -> Result<u32, ZyppError> {
+        let mut inner = self.inner.lock()?;
+        let maybe_medium = inner.attached_media
+        .iter_mut()
+        .find(|x| {
+            match x.1.spec.is_same_medium(&spec) {
+                Indeterminate => urls.first() == x.1.mirrors.first(),
+                val @ ( False | True ) => val.try_into().unwrap()
+            }
+        });
+
+        if let Some(m) = maybe_medium {
+            m.1.use_cnt += 1;
+            return Ok(*m.0);
+        }
+
+        inner.next_attach_id += 1;
+        let id = inner.next_attach_id;
+        inner.attached_media.insert( id, AttachedMedia {
+            use_cnt: 1,
+            attach_dir: tempfile::Builder::new().prefix("zypp-ftp").tempdir()?,
+            mirrors: urls,
+            spec
+        });
+        Ok(id)
+    }
+
+    fn detach( &self, id: u32 ) -> Result<(), ZyppError> {
+        let mut inner = self.inner.lock()?;
+        let medium = inner.attached_media.get_mut(&id).ok_or( MediaError::InvalidHandle )?;
+        medium.use_cnt -= 1;
+        if medium.use_cnt == 0 {
+            inner.attached_media.remove(&id);
+        }
+        Ok(())
+    }
+
+    async fn list_dir( &self, attach_id: u32, path: PathBuf ) -> Result<Vec<String>, ZyppError> {
+        let (_, mirrors) = self.use_medium( attach_id )?;
+        defer!({
+            let _ = self.detach( attach_id );
+        });
+
+        let mut last_result: Option<ZyppError> = None;
+        for mirror in &mirrors {
+            let res = async {
+                let remote_path = MediaFtpDriver::remote_path( mirror, &path )?;
+                let mut conn = FtpConnection::connect( mirror ).await?;
+                let entries = conn.list( &remote_path ).await?;
+                conn.quit().await;
+                Ok::<_, ZyppError>(entries)
+            }.await;
+            match res {
+                Ok(entries) => return Ok(entries),
+                Err(e) => last_result = worse_error( last_result, e )
+            }
+        }
+        Err( last_result.unwrap_or( MediaError::FileNotFound.into() ) )
+    }
+
+    async fn provide( &self, attach_id: u32, path: PathBuf, spec: FileSpec ) -> Result<ProvideStatus, ZyppError> {
+        let (attach_dir, mirrors) = self.use_medium( attach_id )?;
+        // keeps the attach dir alive until we are done
+        defer!({
+            let _ = self.detach( attach_id );
+        });
+
+        if spec.checkExistsOnly {
+            let mut last_result: Option<ZyppError> = None;
+            for mirror in &mirrors {
+                match MediaFtpDriver::file_exists( mirror, &path ).await {
+                    Ok(status) => return Ok(status),
+                    Err(error) => last_result = worse_error( last_result, error )
+                }
+            }
+            return Err( last_result.unwrap_or( MediaError::FileNotFound.into() ) );
+        }
+
+        let target_file_name = path.file_name().and_then( |x| x.to_str() ).ok_or( MediaError::NotAFile )?;
+        let target_path = match path.parent() {
+            Some(parent) => MediaLocalDriver::local_path( &attach_dir, parent )?,
+            None => attach_dir
+        };
+
+        let target_file_path = target_path.join( target_file_name );
+        // files downloaded before without a checksum might not match this one
+        if target_file_path.try_exists()? {
+            match spec.verify_file( &target_file_path ) {
+                Ok(()) => return Ok( ProvideStatus::Provided(target_file_path) ),
+                Err(_) => std::fs::remove_file( &target_file_path )?
+            }
+        }
+        DirBuilder::new().recursive(true).create( &target_path ).await?;
+
+        let mut last_result: Option<ZyppError> = None;
+        for mirror in &mirrors {
+            match MediaFtpDriver::download_file( mirror, &path, &target_path, target_file_name, &spec ).await {
+                Ok(result) => return Ok( ProvideStatus::Provided(result) ),
+                Err(error) => last_result = worse_error( last_result, error )
+            }
+        }
+        Err( last_result.unwrap_or( MediaError::FileNotFound.into() ) )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    const CONTENT: &[u8] = b"hello\n";
+
+    /// Opens a passive data port and returns it with the listener waiting on it
+    async fn data_port() -> std::io::Result<(TcpListener, u16)> {
+        let listener = TcpListener::bind( "127.0.0.1:0" ).await?;
+        let port = listener.local_addr()?.port();
+        Ok( (listener, port) )
+    }
+
+    /// A tiny ftp server knowing only /repo/f.txt, it wants the user zypp with the password secret
+    async fn serve_client( control: TcpStream, epsv: bool ) -> std::io::Result<()> {
+        let (read, mut write) = control.into_split();
+        let mut lines = BufReader::new( read ).lines();
+        let mut passive: Option<TcpListener> = None;
+        write.write_all( b"220 mock ftp\r\n" ).await?;
+
+        while let Some(line) = lines.next_line().await? {
+            let (verb, arg) = line.split_once(' ').unwrap_or( (line.as_str(), "") );
+            let file = Some( CONTENT ).filter( |_| arg == "/repo/f.txt" );
+            let reply = match (verb, file) {
+                ("USER", _) if arg == "zypp" => "331 Password required".to_owned(),
+                ("PASS", _) if arg == "secret" => "230 Logged in".to_owned(),
+                ("USER" | "PASS", _) => "530 Login incorrect".to_owned(),
+                ("TYPE", _) => "200 Switching to binary mode".to_owned(),
+                ("EPSV", _) if epsv => {
+                    let (listener, port) = data_port().await?;
+                    passive = Some( listener );
+                    format!("229 Entering Extended Passive Mode (|||{}|)", port)
+                },
+                ("PASV", _) => {
+                    let (listener, port) = data_port().await?;
+                    passive = Some( listener );
+                    format!("227 Entering Passive Mode (127,0,0,1,{},{})", port / 256, port % 256)
+                },
+                ("SIZE", Some(content)) => format!("213 {}", content.len()),
+                ("MDTM", Some(_)) => "213 20231201120000".to_owned(),
+                ("RETR", Some(content)) => {
+                    write.write_all( b"150 Opening data connection\r\n" ).await?;
+                    let listener = passive.take().expect( "RETR without passive mode" );
+                    let (mut data, _) = listener.accept().await?;
+                    data.write_all( content ).await?;
+                    data.shutdown().await?;
+                    "226 Transfer complete".to_owned()
+                },
+                ("SIZE" | "MDTM" | "RETR", None) => "550 Failed to open file".to_owned(),
+                ("QUIT", _) => {
+                    write.write_all( b"221 Goodbye\r\n" ).await?;
+                    return Ok(());
+                },
+                _ => "500 Unknown command".to_owned()
+            };
+            write.write_all( format!("{}\r\n", reply).as_bytes() ).await?;
+        }
+        Ok(())
+    }
+
+    /// Starts the server and returns the url of the repo on it
+    async fn start_server( epsv: bool, password: &str ) -> Url {
+        let listener = TcpListener::bind( "127.0.0.1:0" ).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn( async move {
+            while let Ok((control, _)) = listener.accept().await {
+                tokio::spawn( serve_client( control, epsv ) );
+            }
+        });
+        Url::parse( &format!("ftp://zypp:{}@127.0.0.1:{}/repo", password, port) ).unwrap()
+    }
+
+    async fn provide( url: Url, path: &str, spec: FileSpec ) -> Result<ProvideStatus, ZyppError> {
+        let driver = MediaFtpDriver::new();
+        let medium = MediaSpec { label: "test".to_owned(), medianr: 1, verify_data_path: None };
+        let id = driver.attach( vec![ url ], medium ).await?;
+        let res = driver.provide( id, PathBuf::from( path ), spec ).await;
+        if let Ok(ProvideStatus::Provided(file)) = &res {
+            assert_eq!( std::fs::read( file ).unwrap(), CONTENT );
+        }
+        res
+    }
+
+    #[tokio::test]
+    async fn downloads_with_pasv_if_epsv_is_not_supported() {
+        let url = start_server( false, "secret" ).await;
+        let res = provide( url, "/f.txt", FileSpec::default() ).await;
+        assert!( matches!( res, Ok( ProvideStatus::Provided(_) ) ), "{:?}", res );
+    }
+
+    #[tokio::test]
+    async fn downloads_with_epsv() {
+        let url = start_server( true, "secret" ).await;
+        let res = provide( url, "/f.txt", FileSpec::default() ).await;
+        assert!( matches!( res, Ok( ProvideStatus::Provided(_) ) ), "{:?}", res );
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_not_found() {
+        let url = start_server( true, "secret" ).await;
+        let res = provide( url, "/missing.txt", FileSpec::default() ).await;
+        assert!( matches!( res, Err( ZyppError::Media { source: MediaError::FileNotFound } ) ), "{:?}", res );
+    }
+
+    #[tokio::test]
+    async fn wrong_password_fails_the_login() {
+        let url = start_server( true, "wrong" ).await;
+        let res = provide( url, "/f.txt", FileSpec::default() ).await;
+        assert!( matches!( res, Err( ZyppError::Media { source: MediaError::FtpError(ref msg) } ) if msg == "Login failed" ), "{:?}", res );
+    }
+
+    #[tokio::test]
+    async fn exists_reports_size_and_mtime() {
+        let url = start_server( false, "secret" ).await;
+        let spec = FileSpec { checkExistsOnly: true, ..Default::default() };
+        let res = provide( url, "/f.txt", spec ).await.unwrap();
+        assert_eq!( res, ProvideStatus::Exists( FileInfo { size: Some( CONTENT.len() as u64 ), mtime: Some( 1701432000 ) } ) );
+    }
+
+    #[test]
+    fn pasv_port() {
+        assert_eq!( parse_pasv( "Entering Passive Mode (127,0,0,1,25,46)" ), Some( 25 * 256 + 46 ) );
+        assert_eq!( parse_pasv( "Entering Passive Mode (10, 0, 0, 1, 255, 255)." ), Some( 65535 ) );
+        // out of range, negative or missing fields are not taken modulo or skipped
+        assert_eq!( parse_pasv( "Entering Passive Mode (127,0,0,1,256,1)" ), None );
+        assert_eq!( parse_pasv( "Entering Passive Mode (127,0,0,1,-1,1)" ), None );
+        assert_eq!( parse_pasv( "Entering Passive Mode (127,0,0,1,x,25,46)" ), None );
+        assert_eq!( parse_pasv( "Entering Passive Mode (127,0,0,1,25)" ), None );
+        assert_eq!( parse_pasv( "Entering Passive Mode" ), None );
+    }
+
+    #[test]
+    fn mdtm_is_utc() {
+        assert_eq!( parse_mdtm( "19700101000000" ), Some( 0 ) );
+        assert_eq!( parse_mdtm( "20240229235959.123" ), Some( 1709251199 ) );
+        assert_eq!( parse_mdtm( "2024" ), None );
+    }
+}
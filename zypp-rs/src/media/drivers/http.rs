@@ -1,22 +1,25 @@
 use async_trait::async_trait;
 use log::info;
-use reqwest::{Client,Response};
+use reqwest::{Client,Response,StatusCode};
+use reqwest::header::{CONTENT_LENGTH, LAST_MODIFIED};
 use tokio::io::AsyncWriteExt;
 use tokio::sync::{Notify, AcquireError, watch};
 use tribool::Tribool::{True,False,Indeterminate};
 use url::Url;
 use std::collections::{HashMap, HashSet};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::sync::atomic::AtomicI64;
 use std::sync::{Arc, Weak, Mutex, PoisonError};
+use std::time::UNIX_EPOCH;
 use tempfile::{TempDir, NamedTempFile};
 use scopeguard::{defer, guard};
 
 use tokio::fs::{DirBuilder, File};
 use futures::StreamExt;
+use percent_encoding::percent_decode_str;
 
 use crate::error::ZyppError;
-use crate::media::{MediaError, driver::MediaDriver, spec::{*}};
+use crate::media::{MediaError, driver::{MediaDriver, worse_error}, spec::{*}};
 
 struct AttachedMedia {
     use_cnt: Arc<AtomicI64>,
@@ -50,39 +53,119 @@ impl MediaHttpDriver {
         }
     }
 
-    async fn download_file<P: AsRef<Path>>( mirror: &Url, path_on_medium: &Path, target_path: P, target_file_name: &str ) -> Result<PathBuf, ZyppError> {
+    /// Paths on the medium are always relative to the mirror url, even if they start with a '/'
+    fn mirror_url( mirror: &Url, path_on_medium: &Path ) -> Result<Url, ZyppError> {
+        let mut req_url = mirror.clone();
+        {
+            let mut segments = req_url.path_segments_mut().map_err( |_| MediaError::InvalidUrl )?;
+            segments.pop_if_empty();
+            for component in path_on_medium.components() {
+                match component {
+                    Component::Normal(c) => { segments.push( c.to_str().ok_or( MediaError::InvalidPath )? ); },
+                    Component::RootDir | Component::CurDir => {},
+                    _ => return Err( MediaError::InvalidPath.into() )
+                }
+            }
+        }
+        Ok(req_url)
+    }
+
+    /// Extracts the entries of a directory index page as generated by apache, nginx and co.
+    /// Only links to direct children are taken, subdirectories keep their trailing '/'.
+    fn parse_index_page( page: &str ) -> Vec<String> {
+        let mut entries: Vec<String> = page.split("href=\"").skip(1)
+            .filter_map( |l| l.split('"').next() )
+            .filter( |href| !href.is_empty() && !href.starts_with(['/', '?', '#', '.']) && !href.contains("://") )
+            .map( |href| percent_decode_str( href ).decode_utf8_lossy().to_string() )
+            .filter( |name| !name.trim_end_matches('/').contains('/') )
+            .collect();
+        entries.sort();
+        entries.dedup();
+        entries
+    }
+
+    /// Downloads the file into a temporary file next to the target, which is only renamed
+    /// to the target once the checksum from the spec matches
+    async fn download_file<P: AsRef<Path>>( mirror: &Url, path_on_medium: &Path, target_path: P, target_file_name: &str, spec: &FileSpec ) -> Result<PathBuf, ZyppError> {
 
         let target_file_path = target_path.as_ref().join(target_file_name);
         let tmp_file = NamedTempFile::new_in( &target_path )?.into_temp_path();
 
         info!("Downloading into tmp path: {}", tmp_file.to_str().unwrap_or_default() );
 
-        let req_url = mirror.join( path_on_medium.to_str().ok_or( MediaError::InvalidPath)? ).map_err( |_| MediaError::InvalidPath )?;
+        let req_url = Self::mirror_url( mirror, path_on_medium )?;
 
         // open the file and truncate it
         let mut file = File::create( &tmp_file ).await?;
 
         let res = Client::new().get(req_url.clone()).send().await.map_err( MediaError::from )?;
         if res.status().is_success() {
+            // no need to start if the server already tells us the file is too big
+            if let Some(len) = res.content_length() {
+                spec.check_download_size( len )?;
+            }
+
             let mut stream = res.bytes_stream();
+            let mut hasher = spec.checksum_hasher();
+            let mut downloaded: u64 = 0;
 
-            // here we should also track downloaded bytes
             while let Some(item) = stream.next().await {
-                file.write( &item.map_err( MediaError::from )? ).await?;
+                let item = item.map_err( MediaError::from )?;
+                downloaded += item.len() as u64;
+                spec.check_download_size( downloaded )?;
+                if let Some(hasher) = hasher.as_mut() {
+                    hasher.update( &item );
+                }
+                file.write_all( &item ).await?;
             }
 
             // yay we got the file
             // make sure its synced to the disk
             file.sync_all().await?;
 
+            // a broken file is never persisted, the temp file is removed when dropped
+            spec.verify_checksum( hasher )?;
+
             //@todo what if the file cannot be persistet?
             tmp_file.persist( &target_file_path ).map_err(|e| e.error )?;
 
             return Ok(target_file_path.to_owned());
         }
 
-        // @todo match Reqwest status to actual error... e.g Auth, NotFound etc
-        Err(MediaError::FileNotFound.into())
+        Err( Self::status_error( res.status() ) )
+    }
+
+    /// Only a file the server says is not there is a FileNotFound, so optional files are not
+    /// skipped because of e.g. a missing login or an overloaded server
+    fn status_error( status: StatusCode ) -> ZyppError {
+        match status {
+            StatusCode::NOT_FOUND | StatusCode::GONE => MediaError::FileNotFound.into(),
+            status => MediaError::HttpStatus( status.as_u16() ).into()
+        }
+    }
+
+    /// Checks if the file exists on one of the mirrors with a HEAD request, nothing is downloaded.
+    /// Size and modification time are taken from the Content-Length and Last-Modified headers.
+    async fn file_exists( mirrors: &[Url], path_on_medium: &Path ) -> Result<ProvideStatus, ZyppError> {
+        let mut last_result: Option<ZyppError> = None;
+        for mirror in mirrors {
+            let req_url = Self::mirror_url( mirror, path_on_medium )?;
+            match Client::new().head( req_url ).send().await.map_err( MediaError::from ) {
+                Ok(res) if res.status().is_success() => {
+                    let header = |name| res.headers().get( name ).and_then( |v| v.to_str().ok() );
+                    // content_length() is the size of the body, which HEAD responses do not have
+                    let size = header( CONTENT_LENGTH ).and_then( |v| v.parse().ok() );
+                    let mtime = header( LAST_MODIFIED )
+                        .and_then( |v| httpdate::parse_http_date( v ).ok() )
+                        .and_then( |t| t.duration_since( UNIX_EPOCH ).ok() )
+                        .map( |d| d.as_secs() );
+                    return Ok( ProvideStatus::Exists( FileInfo { size, mtime } ) );
+                },
+                Ok(res) => last_result = worse_error( last_result, Self::status_error( res.status() ) ),
+                Err(e) => last_result = worse_error( last_result, e.into() )
+            }
+        }
+        Err( last_result.unwrap_or( MediaError::FileNotFound.into() ) )
     }
 }
 
@@ -147,10 +230,38 @@ impl MediaDriver for MediaHttpDriver {
         }
     }
 
-    async fn provide( &self, attachId: u32, path: PathBuf, _spec: FileSpec ) -> Result<PathBuf, ZyppError> {
+    async fn list_dir( &self, attachId: u32, path: PathBuf ) -> Result<Vec<String>, ZyppError> {
+        let mirrors = {
+            let medium = self.inner.attached_media.lock()?;
+            medium.get(&attachId).ok_or( MediaError::InvalidHandle )?.mirrors.clone()
+        };
+
+        let mut lastResult: Option<ZyppError> = None;
+        for mirror in &mirrors {
+            let mut dir_url = MediaHttpDriver::mirror_url( mirror, &path )?;
+            // directory indexes are only served for urls with a trailing slash
+            if let Ok(mut segments) = dir_url.path_segments_mut() {
+                segments.pop_if_empty().push("");
+            }
+
+            let res = Client::new().get( dir_url ).send().await.map_err( MediaError::from );
+            match res {
+                Ok(res) if res.status().is_success() => {
+                    let page = res.text().await.map_err( MediaError::from )?;
+                    return Ok( MediaHttpDriver::parse_index_page( &page ) );
+                },
+                Ok(res) => lastResult = worse_error( lastResult, MediaHttpDriver::status_error( res.status() ) ),
+                Err(e) => lastResult = worse_error( lastResult, e.into() )
+            }
+        }
+        Err( lastResult.unwrap_or( MediaError::FileNotFound.into() ) )
+    }
+
+    async fn provide( &self, attachId: u32, path: PathBuf, spec: FileSpec ) -> Result<ProvideStatus, ZyppError> {
 
         let lock;
         let mut targetPath;
+        let all_mirrors;
 
         {
             let mut medium = self.inner.attached_media.lock()?;
@@ -161,6 +272,7 @@ impl MediaDriver for MediaHttpDriver {
             lock.fetch_add(1, std::sync::atomic::Ordering::Acquire );
 
             targetPath = handle.attach_dir.path().to_owned();
+            all_mirrors = handle.mirrors.clone();
         }
 
         // release the handle once we are done with it
@@ -182,6 +294,10 @@ impl MediaDriver for MediaHttpDriver {
             }
         }
 
+        if spec.checkExistsOnly {
+            return MediaHttpDriver::file_exists( &all_mirrors, &path ).await;
+        }
+
         // if the target dir does not exist, create it
         if !targetPath.try_exists()? {
 
@@ -223,9 +339,13 @@ impl MediaDriver for MediaHttpDriver {
 
         loop {
 
-            // if the target file is already there -> use it
+            // if the target file is already there -> use it, unless it was
+            // downloaded before without a checksum and does not match this one
             if target_file_path.try_exists()? {
-                return Ok(target_file_path);
+                match spec.verify_file( &target_file_path ) {
+                    Ok(()) => return Ok( ProvideStatus::Provided(target_file_path) ),
+                    Err(_) => std::fs::remove_file( &target_file_path )?
+                }
             }
 
             // we need to check if a request is already running
@@ -252,13 +372,13 @@ impl MediaDriver for MediaHttpDriver {
                 let mut lastResult: Option<ZyppError> = None;
                 for url in &mirrors.unwrap() {
 
-                    let res: Result<PathBuf, ZyppError> = MediaHttpDriver::download_file(url, &path, &targetPath, &target_file_name).await;
+                    let res: Result<PathBuf, ZyppError> = MediaHttpDriver::download_file(url, &path, &targetPath, &target_file_name, &spec).await;
                     match res {
                         Ok( result ) => {
-                            return Ok(result);
+                            return Ok( ProvideStatus::Provided(result) );
                         },
                         Err(error) =>  {
-                            lastResult = Some(error);
+                            lastResult = worse_error( lastResult, error );
                         }
                     }
                 }
@@ -281,3 +401,108 @@ impl Drop for MediaHttpDriverShared {
 
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+    use crate::checksum::{CheckSum, CheckSumType};
+
+    /// Answers every request for path with the status and body of the route, 404 if there is none
+    async fn serve_client( stream: TcpStream, routes: &[(&str, u16, &str)] ) -> std::io::Result<()> {
+        let mut stream = BufReader::new( stream );
+        let mut request = String::new();
+        stream.read_line( &mut request ).await?;
+        let mut header = String::new();
+        while stream.read_line( &mut header ).await? > 2 {
+            header.clear();
+        }
+        let path = request.split(' ').nth(1).unwrap_or_default();
+        let (status, body) = routes.iter().find( |r| r.0 == path ).map_or( (404, ""), |r| (r.1, r.2) );
+        let body = if request.starts_with("HEAD") { "" } else { body };
+        let response = format!("HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body);
+        stream.get_mut().write_all( response.as_bytes() ).await?;
+        stream.get_mut().shutdown().await
+    }
+
+    async fn start_server( routes: &'static [(&'static str, u16, &'static str)] ) -> Url {
+        let listener = TcpListener::bind( "127.0.0.1:0" ).await.unwrap();
+        let url = Url::parse( &format!("http://{}/", listener.local_addr().unwrap()) ).unwrap();
+        tokio::spawn( async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn( serve_client( stream, routes ) );
+            }
+        });
+        url
+    }
+
+    /// The driver is returned as well, provided files are removed together with it
+    async fn provide( mirrors: Vec<Url>, path: &str, spec: FileSpec ) -> (MediaHttpDriver, Result<ProvideStatus, ZyppError>) {
+        let driver = MediaHttpDriver::new();
+        let medium = MediaSpec { label: "test".to_owned(), medianr: 1, verify_data_path: None };
+        let id = driver.attach( mirrors, medium ).await.unwrap();
+        let res = driver.provide( id, PathBuf::from( path ), spec ).await;
+        (driver, res)
+    }
+
+    const ROUTES: &[(&str, u16, &str)] = &[
+        ("/broken/f.txt", 200, "broken\n"),
+        ("/good/f.txt", 200, "hello\n"),
+        ("/good/down.txt", 503, ""),
+        ("/good/secret.txt", 403, ""),
+        ("/good/gone.txt", 410, ""),
+        ("/good/big.bin", 200, "0123456789"),
+    ];
+
+    #[tokio::test]
+    async fn only_missing_files_are_not_found() {
+        let base = start_server( ROUTES ).await;
+        let mirror = vec![ base.join("good/").unwrap() ];
+        for check_exists_only in [ false, true ] {
+            let spec = FileSpec { checkExistsOnly: check_exists_only, ..Default::default() };
+            for (path, status) in [ ("/down.txt", Some( 503 )), ("/secret.txt", Some( 403 )), ("/gone.txt", None), ("/missing.txt", None) ] {
+                let (_driver, res) = provide( mirror.clone(), path, spec.clone() ).await;
+                match status {
+                    Some(code) => assert!( matches!( res, Err( ZyppError::Media { source: MediaError::HttpStatus(c) } ) if c == code ), "{}: {:?}", path, res ),
+                    None => assert!( matches!( res, Err( ZyppError::Media { source: MediaError::FileNotFound } ) ), "{}: {:?}", path, res ),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn broken_file_is_reported_over_missing_file() {
+        let base = start_server( ROUTES ).await;
+        let mirrors = vec![ base.join("broken/").unwrap(), base.join("missing/").unwrap() ];
+        let checksum = CheckSum::new( CheckSumType::Sha256, "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03" ).unwrap();
+        let spec = FileSpec { checksum: Some( checksum ), ..Default::default() };
+        let (_driver, res) = provide( mirrors, "/f.txt", spec ).await;
+        assert!( matches!( res, Err( ZyppError::Media { source: MediaError::ChecksumMismatch { .. } } ) ), "{:?}", res );
+    }
+
+    #[tokio::test]
+    async fn next_mirror_is_used_for_broken_file() {
+        let base = start_server( ROUTES ).await;
+        let mirrors = vec![ base.join("broken/").unwrap(), base.join("good/").unwrap() ];
+        let checksum = CheckSum::new( CheckSumType::Sha256, "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03" ).unwrap();
+        let spec = FileSpec { checksum: Some( checksum ), ..Default::default() };
+        let (_driver, res) = provide( mirrors, "/f.txt", spec ).await;
+        let Ok(ProvideStatus::Provided(file)) = res else {
+            panic!("The file of the second mirror was not provided");
+        };
+        assert_eq!( std::fs::read_to_string( file ).unwrap(), "hello\n" );
+    }
+
+    #[tokio::test]
+    async fn file_over_the_download_size_is_not_kept() {
+        let base = start_server( ROUTES ).await;
+        let spec = FileSpec { downloadSize: byte_unit::Byte::from_bytes( 5 ), ..Default::default() };
+        let (driver, res) = provide( vec![ base.join("good/").unwrap() ], "/big.bin", spec ).await;
+        assert!( matches!( res, Err( ZyppError::Media { source: MediaError::DownloadSizeExceeded(5) } ) ), "{:?}", res );
+
+        let media = driver.inner.attached_media.lock().unwrap();
+        let attach_dir = media.values().next().unwrap().attach_dir.path();
+        assert_eq!( std::fs::read_dir( attach_dir ).unwrap().count(), 0 );
+    }
+}
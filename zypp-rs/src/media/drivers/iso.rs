@@ -0,0 +1,93 @@
+use async_trait::async_trait;
+use url::Url;
+use std::path::{Path, PathBuf};
+
+use crate::error::ZyppError;
+use crate::media::{MediaError, driver::MediaDriver, spec::{*}};
+use super::local::MediaLocalDriver;
+use super::mount::Mount;
+
+/// Serves files out of ISO9660 images, which are loop mounted while the medium is attached,
+/// so this needs to run as root. The urls look like libzypp's:
+/// iso:/subdir?iso=image.iso&url=dir:/srv/images, where url is the local medium the image
+/// is on and the path is the directory inside the image. Without url, iso is an absolute path.
+pub struct MediaIsoDriver {
+    /// the mounted images are local directories, the local driver does the bookkeeping
+    files: MediaLocalDriver
+}
+
+impl MediaIsoDriver {
+
+    pub fn new() -> Self {
+        Self { files: MediaLocalDriver::new() }
+    }
+
+    async fn open_iso( url: Url ) -> Result<(PathBuf, Vec<Mount>), ZyppError> {
+        let iso = MediaLocalDriver::query_param( &url, "iso" ).ok_or( MediaError::InvalidUrl )?;
+        let dir_in_image = url.to_file_path().map_err( |_| MediaError::InvalidUrl )?;
+
+        // the medium the image is on, mounted as well if it is a device
+        let (iso_path, mut mounts) = match MediaLocalDriver::query_param( &url, "url" ) {
+            Some(iso_url) => {
+                let iso_url = Url::parse( &iso_url ).map_err( |_| MediaError::InvalidUrl )?;
+                if !matches!( iso_url.scheme(), "file" | "dir" | "hd" ) {
+                    return Err( MediaError::NotSupported.into() );
+                }
+                let (root, mounts) = MediaLocalDriver::open_medium( iso_url ).await?;
+                match MediaLocalDriver::local_path( &root, Path::new( &iso ) ) {
+                    Ok(iso_path) => ( iso_path, mounts ),
+                    Err(e) => {
+                        Mount::unmount_all( mounts );
+                        return Err(e);
+                    }
+                }
+            },
+            None => ( PathBuf::from( iso ), Vec::new() )
+        };
+
+        let fs_type = MediaLocalDriver::query_param( &url, "filesystem" ).unwrap_or( "iso9660".to_owned() );
+        let image = match iso_path.is_file() {
+            true => Mount::new( &iso_path.to_string_lossy(), Some( &fs_type ), &["loop"] ).await,
+            false => Err( MediaError::FileNotFound.into() )
+        };
+        let root = image.and_then( |image| {
+            // the image has to be unmounted before the medium it is on
+            mounts.insert( 0, image );
+            MediaLocalDriver::local_path( mounts[0].path(), &dir_in_image )
+        });
+        match root {
+            Ok(root) if root.is_dir() => Ok( (root, mounts) ),
+            Ok(_) => {
+                Mount::unmount_all( mounts );
+                Err( MediaError::FileNotFound.into() )
+            },
+            Err(e) => {
+                Mount::unmount_all( mounts );
+                Err(e)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl MediaDriver for MediaIsoDriver {
+    fn schemes( &self ) -> Vec<String> {
+        vec!["iso".to_owned()]
+    }
+
+    async fn attach( &self, urls: Vec<Url>, spec: MediaSpec ) -> Result<u32, ZyppError> {
+        self.files.attach_with( urls, spec, MediaIsoDriver::open_iso ).await
+    }
+
+    fn detach( &self, id: u32 ) -> Result<(), ZyppError> {
+        self.files.detach( id )
+    }
+
+    async fn list_dir( &self, attach_id: u32, path: PathBuf ) -> Result<Vec<String>, ZyppError> {
+        self.files.list_dir( attach_id, path ).await
+    }
+
+    async fn provide( &self, attach_id: u32, path: PathBuf, spec: FileSpec ) -> Result<ProvideStatus, ZyppError> {
+        self.files.provide( attach_id, path, spec ).await
+    }
+}
@@ -0,0 +1,292 @@
+use async_trait::async_trait;
+use tribool::Tribool::{True,False,Indeterminate};
+use url::Url;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::error::ZyppError;
+use crate::media::{MediaError, driver::{MediaDriver, worse_error}, spec::{*}};
+use super::mount::Mount;
+
+struct AttachedMedia {
+    use_cnt: u32,
+    /// the local directories of the urls of the medium that could be opened, in the order
+    /// of the urls. Files are taken from the first one that has them with the right checksum.
+    roots: Vec<PathBuf>,
+    /// what was mounted for the medium, like the device of hd urls, unmounted when the
+    /// medium is detached. Mounts inside other mounts come first, so they are unmounted first.
+    mounts: Vec<Mount>,
+    mirrors: Vec<Url>,
+    spec: MediaSpec
+}
+
+struct MediaLocalDriverShared {
+    next_attach_id: u32,
+    attached_media: HashMap<u32, AttachedMedia>
+}
+
+/// Serves files of the file, dir and hd schemes straight from the local filesystem,
+/// without copying them. hd urls name a device in the query, like
+/// hd:/repo?device=/dev/sdb1&filesystem=ext4, which is mounted while the medium is attached.
+pub struct MediaLocalDriver {
+    inner: Mutex<MediaLocalDriverShared>
+}
+
+impl MediaLocalDriver {
+
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new( MediaLocalDriverShared { next_attach_id: 1, attached_media: Default::default() } )
+        }
+    }
+
+    /// Paths on the medium are always relative to the medium root, even if they start with a '/'
+    pub(crate) fn local_path( root: &Path, path_on_medium: &Path ) -> Result<PathBuf, ZyppError> {
+        let mut res = root.to_owned();
+        for component in path_on_medium.components() {
+            match component {
+                Component::Normal(c) => res.push( c ),
+                Component::RootDir | Component::CurDir => {},
+                _ => return Err( MediaError::InvalidPath.into() )
+            }
+        }
+        Ok(res)
+    }
+
+    pub(crate) fn query_param( url: &Url, name: &str ) -> Option<String> {
+        url.query_pairs().find( |(k, _)| k == name ).map( |(_, v)| v.to_string() )
+    }
+
+    /// Finds the local directory of the medium, mounting the device of hd urls first
+    pub(crate) async fn open_medium( url: Url ) -> Result<(PathBuf, Vec<Mount>), ZyppError> {
+        let path = url.to_file_path().map_err( |_| MediaError::InvalidUrl )?;
+
+        let mut mounts = Vec::new();
+        let root = match Self::query_param( &url, "device" ) {
+            Some(device) if url.scheme() == "hd" => {
+                let fs_type = Self::query_param( &url, "filesystem" );
+                let mount = Mount::new( &device, fs_type.as_deref(), &[] ).await?;
+                let root = Self::local_path( mount.path(), &path );
+                mounts.push( mount );
+                match root {
+                    Ok(root) => root,
+                    Err(e) => {
+                        Mount::unmount_all( mounts );
+                        return Err(e);
+                    }
+                }
+            },
+            _ => path
+        };
+
+        if !root.is_dir() {
+            Mount::unmount_all( mounts );
+            return Err( MediaError::FileNotFound.into() );
+        }
+        Ok( (root, mounts) )
+    }
+
+    /// Attaches the medium, open finds the local directory of an url and mounts what is needed
+    /// for it. All urls are opened, the attach only fails if none of them works. Drivers for
+    /// other local media like iso images reuse everything but this step.
+    pub(crate) async fn attach_with<F, Fut>( &self, urls: Vec<Url>, spec: MediaSpec, open: F ) -> Result<u32, ZyppError>
+        where F: Fn(Url) -> Fut, Fut: Future<Output = Result<(PathBuf, Vec<Mount>), ZyppError>> {
+        {
+            let mut inner = self.inner.lock()?;
+            let maybe_medium = inner.attached_media
+            .iter_mut()
+            .find(|x| {
+                match x.1.spec.is_same_medium(&spec) {
+                    Indeterminate => urls.first() == x.1.mirrors.first(),
+                    val @ ( False | True ) => val.try_into().unwrap()
+                }
+            });
+
+            if let Some(m) = maybe_medium {
+                m.1.use_cnt += 1;
+                return Ok(*m.0);
+            }
+        }
+
+        // every url that works is kept, so a broken copy of a file can be taken from the next one
+        let mut roots = Vec::new();
+        let mut all_mounts = Vec::new();
+        let mut last_result: Option<ZyppError> = None;
+        for url in &urls {
+            match open( url.clone() ).await {
+                Ok((root, mounts)) => {
+                    roots.push( root );
+                    all_mounts.extend( mounts );
+                },
+                Err(e) => last_result = worse_error( last_result, e )
+            }
+        }
+        if roots.is_empty() {
+            return Err( last_result.unwrap_or( MediaError::InvalidUrl.into() ) );
+        }
+
+        let mut inner = self.inner.lock()?;
+        inner.next_attach_id += 1;
+        let id = inner.next_attach_id;
+        inner.attached_media.insert( id, AttachedMedia {
+            use_cnt: 1,
+            roots,
+            mounts: all_mounts,
+            mirrors: urls,
+            spec
+        });
+        Ok(id)
+    }
+
+    fn medium_roots( &self, attach_id: u32 ) -> Result<Vec<PathBuf>, ZyppError> {
+        let inner = self.inner.lock()?;
+        Ok( inner.attached_media.get(&attach_id).ok_or( MediaError::InvalidHandle )?.roots.clone() )
+    }
+
+    async fn list_dir_in( root: &Path, path: &Path ) -> Result<Vec<String>, ZyppError> {
+        let dir = MediaLocalDriver::local_path( root, path )?;
+
+        let mut read_dir = match tokio::fs::read_dir( &dir ).await {
+            Ok(r) => r,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Err( MediaError::FileNotFound.into() ),
+            Err(e) => return Err( e.into() )
+        };
+        let mut entries = Vec::new();
+        while let Some(entry) = read_dir.next_entry().await? {
+            let mut name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type().await?.is_dir() {
+                name.push('/');
+            }
+            entries.push( name );
+        }
+        entries.sort();
+        Ok(entries)
+    }
+
+    fn provide_from( root: &Path, path: &Path, spec: &FileSpec ) -> Result<ProvideStatus, ZyppError> {
+        let file = MediaLocalDriver::local_path( root, path )?;
+
+        if !file.try_exists()? {
+            return Err( MediaError::FileNotFound.into() );
+        }
+        if !file.is_file() {
+            return Err( MediaError::NotAFile.into() );
+        }
+        if spec.checkExistsOnly {
+            let meta = file.metadata()?;
+            let mtime = meta.modified().ok()
+                .and_then( |t| t.duration_since( UNIX_EPOCH ).ok() )
+                .map( |d| d.as_secs() );
+            return Ok( ProvideStatus::Exists( FileInfo { size: Some( meta.len() ), mtime } ) );
+        }
+        spec.verify_file( &file )?;
+        Ok( ProvideStatus::Provided(file) )
+    }
+}
+
+impl Drop for MediaLocalDriver {
+    fn drop( &mut self ) {
+        if let Ok(inner) = self.inner.get_mut() {
+            inner.attached_media.drain().for_each( |(_, medium)| Mount::unmount_all( medium.mounts ) );
+        }
+    }
+}
+
+#[async_trait]
+impl MediaDriver for MediaLocalDriver {
+    fn schemes( &self ) -> Vec<String> {
+        vec!["file".to_owned(), "dir".to_owned(), "hd".to_owned()]
+    }
+
+    async fn attach( &self, urls: Vec<Url>, spec: MediaSpec ) -> Result<u32, ZyppError> {
+        self.attach_with( urls, spec, MediaLocalDriver::open_medium ).await
+    }
+
+    fn detach( &self, id: u32 ) -> Result<(), ZyppError> {
+        let released = {
+            let mut inner = self.inner.lock()?;
+            let medium = inner.attached_media.get_mut(&id).ok_or( MediaError::InvalidHandle )?;
+            medium.use_cnt -= 1;
+            if medium.use_cnt > 0 {
+                return Ok(());
+            }
+            inner.attached_media.remove(&id)
+        };
+        // unmount without holding the lock, other media stay usable meanwhile
+        if let Some(medium) = released {
+            Mount::unmount_all( medium.mounts );
+        }
+        Ok(())
+    }
+
+    async fn list_dir( &self, attach_id: u32, path: PathBuf ) -> Result<Vec<String>, ZyppError> {
+        let mut last_result: Option<ZyppError> = None;
+        for root in self.medium_roots( attach_id )? {
+            match MediaLocalDriver::list_dir_in( &root, &path ).await {
+                Ok(entries) => return Ok(entries),
+                Err(e) => last_result = worse_error( last_result, e )
+            }
+        }
+        Err( last_result.unwrap_or( MediaError::FileNotFound.into() ) )
+    }
+
+    async fn provide( &self, attach_id: u32, path: PathBuf, spec: FileSpec ) -> Result<ProvideStatus, ZyppError> {
+        let mut last_result: Option<ZyppError> = None;
+        for root in self.medium_roots( attach_id )? {
+            match MediaLocalDriver::provide_from( &root, &path, &spec ) {
+                Ok(status) => return Ok(status),
+                Err(e) => last_result = worse_error( last_result, e )
+            }
+        }
+        Err( last_result.unwrap_or( MediaError::FileNotFound.into() ) )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::{CheckSum, CheckSumType};
+
+    fn spec() -> MediaSpec {
+        MediaSpec { label: "test".to_owned(), medianr: 1, verify_data_path: None }
+    }
+
+    fn dir_entries( dir: &Path ) -> Vec<PathBuf> {
+        let mut entries: Vec<PathBuf> = std::fs::read_dir( dir ).unwrap().map( |e| e.unwrap().path() ).collect();
+        entries.sort();
+        entries
+    }
+
+    #[tokio::test]
+    async fn checksum_mismatch_tries_the_next_root() {
+        let broken = tempfile::tempdir().unwrap();
+        let good = tempfile::tempdir().unwrap();
+        std::fs::write( broken.path().join("f.txt"), "broken\n" ).unwrap();
+        std::fs::write( good.path().join("f.txt"), "hello\n" ).unwrap();
+
+        let driver = MediaLocalDriver::new();
+        let urls = vec![ Url::from_directory_path( broken.path() ).unwrap(), Url::from_directory_path( good.path() ).unwrap() ];
+        let id = driver.attach( urls, spec() ).await.unwrap();
+
+        // sha256 of "hello\n"
+        let checksum = CheckSum::new( CheckSumType::Sha256, "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03" ).unwrap();
+        let file_spec = FileSpec { checksum: Some( checksum ), ..Default::default() };
+        let res = driver.provide( id, PathBuf::from("/f.txt"), file_spec ).await.unwrap();
+        assert_eq!( res, ProvideStatus::Provided( good.path().join("f.txt") ) );
+
+        // no root has the file with the sha256 of "other\n", nothing is provided or left behind
+        let checksum = CheckSum::new( CheckSumType::Sha256, "7e4fa2eb8c7ac089739d5defc4489fad68a100d92082ca35c6b40a4524821f87" ).unwrap();
+        let file_spec = FileSpec { checksum: Some( checksum ), ..Default::default() };
+        let res = driver.provide( id, PathBuf::from("/f.txt"), file_spec ).await;
+        assert!( matches!( res, Err( ZyppError::Media { source: MediaError::ChecksumMismatch { .. } } ) ), "{:?}", res );
+        assert_eq!( dir_entries( broken.path() ), vec![ broken.path().join("f.txt") ] );
+        assert_eq!( dir_entries( good.path() ), vec![ good.path().join("f.txt") ] );
+        assert_eq!( std::fs::read_to_string( broken.path().join("f.txt") ).unwrap(), "broken\n" );
+
+        driver.detach( id ).unwrap();
+    }
+}
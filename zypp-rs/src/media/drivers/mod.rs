@@ -1 +1,5 @@
-pub mod http;
\ No newline at end of file
+pub mod ftp;
+pub mod http;
+pub mod iso;
+pub mod local;
+mod mount;
@@ -0,0 +1,63 @@
+use log::warn;
+use std::path::Path;
+use std::process::Command as StdCommand;
+use tempfile::TempDir;
+use tokio::process::Command;
+
+use crate::error::ZyppError;
+use crate::media::MediaError;
+
+/// A filesystem mounted on a temporary directory, it is unmounted again when dropped
+pub(crate) struct Mount {
+    mount_point: TempDir
+}
+
+impl Mount {
+
+    /// Mounts source, a device or an image file, read only. Without a filesystem type
+    /// mount detects it by itself.
+    pub async fn new( source: &str, fs_type: Option<&str>, options: &[&str] ) -> Result<Self, ZyppError> {
+        let mount_point = tempfile::Builder::new().prefix("zypp-mount").tempdir()?;
+
+        let mut cmd = Command::new("mount");
+        if let Some(fs_type) = fs_type.filter( |t| *t != "auto" ) {
+            cmd.arg("-t").arg(fs_type);
+        }
+        let mut all_options = vec!["ro"];
+        all_options.extend_from_slice( options );
+        cmd.arg("-o").arg( all_options.join(",") ).arg( source ).arg( mount_point.path() );
+
+        let output = cmd.output().await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy( &output.stderr ).trim().to_owned();
+            return Err( MediaError::MountFailed( format!("{}: {}", source, stderr) ).into() );
+        }
+        Ok( Self { mount_point } )
+    }
+
+    pub fn path( &self ) -> &Path {
+        self.mount_point.path()
+    }
+
+    /// Unmounts the mounts in the given order. umount waits for the device, so inside the
+    /// async runtime this happens on a blocking thread instead of a worker.
+    pub fn unmount_all( mounts: Vec<Mount> ) {
+        if mounts.is_empty() {
+            return;
+        }
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => { runtime.spawn_blocking( move || drop( mounts ) ); },
+            Err(_) => drop( mounts )
+        }
+    }
+}
+
+impl Drop for Mount {
+    fn drop(&mut self) {
+        // the TempDir can only remove the mount point once nothing is mounted on it anymore
+        match StdCommand::new("umount").arg( self.mount_point.path() ).status() {
+            Ok(status) if status.success() => {},
+            _ => warn!("Failed to unmount {}", self.mount_point.path().display())
+        }
+    }
+}
@@ -9,8 +9,11 @@ use tokio::sync::{ mpsc, oneshot};
 
 use crate::error::ZyppError;
 use crate::media::driver::MediaDriver;
-use crate::media::spec::{FileSpec,MediaSpec};
+use crate::media::spec::{FileSpec,MediaSpec,ProvideStatus};
+use crate::media::drivers::ftp::MediaFtpDriver;
 use crate::media::drivers::http::MediaHttpDriver;
+use crate::media::drivers::iso::MediaIsoDriver;
+use crate::media::drivers::local::MediaLocalDriver;
 
 use super::MediaError;
 
@@ -29,11 +32,16 @@ enum ToWorkerMsg {
         spec: MediaSpec
     },
     Fetch {
-        res_rx: oneshot::Sender<Result<PathBuf, ZyppError>>,
+        res_rx: oneshot::Sender<Result<ProvideStatus, ZyppError>>,
         attachId: u32,
         path: PathBuf,
         spec: FileSpec
     },
+    ListDir {
+        res_rx: oneshot::Sender<Result<Vec<String>, ZyppError>>,
+        attachId: u32,
+        path: PathBuf
+    },
     Detach {
         attachId: u32
     },
@@ -83,6 +91,10 @@ impl Worker {
                 let res = self.driver.provide( attachId, path, spec ).await;
                 res_rx.send( res );
             },
+            ToWorkerMsg::ListDir { res_rx, attachId, path } => {
+                let res = self.driver.list_dir( attachId, path ).await;
+                res_rx.send( res );
+            },
             ToWorkerMsg::Detach { attachId } => {
                 if let Err(e) = self.driver.detach( attachId ) {
                     warn!("Detached unknown id{}", attachId);
@@ -135,24 +147,64 @@ impl Drop for AttachedMedium {
         match self.parent.upgrade() {
             Some(ref v) => {
                 let res = v.lock();
-                if res.is_ok()  {
-                    res.unwrap().detach(self);
+                if let Ok(mut data) = res {
+                    data.detach(self);
                 }
             },
+            // the manager is gone, the drivers and their media with it
             None => {}
         }
     }
 }
 
+/// Diagnostic information about a medium that is currently attached, see Manager::attached_media
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachedMediumInfo {
+    pub driver_id: u32,
+    pub id: u32,
+    pub base_url: Url,
+    pub label: String,
+    /// number of AttachedMedium handles that still refer to the medium
+    pub handles: usize
+}
+
 #[derive(Default)]
 struct ManagerData {
     next_driver_id: u32,
-    drivers: HashMap<u32, WorkerHandle>
+    drivers: HashMap<u32, WorkerHandle>,
+    attached: HashMap<(u32, u32), AttachedMediumInfo>
 }
 
 impl ManagerData {
-    pub fn detach( &self, medium: &AttachedMedium ) {
+    /// Registers a new handle to a medium, attaching the same medium twice gives the same id
+    fn register( &mut self, medium: &AttachedMedium, label: &str ) {
+        let info = self.attached.entry( (medium.driver_id, medium.id) ).or_insert_with( || AttachedMediumInfo {
+            driver_id: medium.driver_id,
+            id: medium.id,
+            base_url: medium.base_url.clone(),
+            label: label.to_owned(),
+            handles: 0
+        });
+        info.handles += 1;
+    }
+
+    /// Releases one handle of the medium, every attach on the driver is paired with a detach.
+    /// The driver releases the medium and its cached files when the last one is gone.
+    pub fn detach( &mut self, medium: &AttachedMedium ) {
+        if let Some(info) = self.attached.get_mut( &(medium.driver_id, medium.id) ) {
+            info.handles -= 1;
+            if info.handles == 0 {
+                self.attached.remove( &(medium.driver_id, medium.id) );
+            }
+        }
 
+        let Some(worker) = self.drivers.get( &medium.driver_id ) else {
+            warn!("Detaching medium {} from unknown driver {}", medium.id, medium.driver_id);
+            return;
+        };
+        if worker.tx.send( ToWorkerMsg::Detach { attachId: medium.id } ).is_err() {
+            warn!("Failed to detach medium {}, the worker is gone", medium.id);
+        }
     }
 }
 
@@ -164,6 +216,9 @@ impl Manager {
     pub fn new() -> Self {
         let me = Self { data: Arc::new(Mutex::new(ManagerData{ ..Default::default() })) };
         me.add_driver( Box::new(MediaHttpDriver::new()) );
+        me.add_driver( Box::new(MediaLocalDriver::new()) );
+        me.add_driver( Box::new(MediaIsoDriver::new()) );
+        me.add_driver( Box::new(MediaFtpDriver::new()) );
         return me;
     }
 
@@ -191,34 +246,71 @@ impl Manager {
         if let Some((driver_id, url, rx)) = resRx {
             return rx.await.map_err(|e|MediaError::WorkerBroken(e.to_string()) )?
             .and_then( |id| {
-                Ok(AttachedMedium{
+                let medium = AttachedMedium{
                     driver_id: driver_id,
                     base_url: url,
                     id: id,
                     parent: Arc::downgrade(&self.data)
-                })
+                };
+                self.data.lock()?.register( &medium, &spec.label );
+                Ok(medium)
             });
         }
 
         return Err(MediaError::NoDriverFound.into());
     }
 
+    /// Downloads a file from the medium and returns its local path. Use provide for optional
+    /// files and existence checks, here a file that is not downloaded is a FileNotFound error.
     pub async fn fetch<P: AsRef<Path>> ( &self, medium: &AttachedMedium, path: P, fileSpec: &FileSpec) -> Result<PathBuf, ZyppError> {
+        match self.provide( medium, path, fileSpec ).await? {
+            ProvideStatus::Provided(file) => Ok(file),
+            ProvideStatus::Exists(_) | ProvideStatus::NotFound => Err( MediaError::FileNotFound.into() )
+        }
+    }
+
+    /// Provides a file from the medium like libzypp's provider: with checkExistsOnly the file is
+    /// only checked for existence, and optional files that are not there are ProvideStatus::NotFound
+    /// instead of an error.
+    pub async fn provide<P: AsRef<Path>> ( &self, medium: &AttachedMedium, path: P, file_spec: &FileSpec) -> Result<ProvideStatus, ZyppError> {
         let mut resRx = None;
         {
             let mut_data = self.data.lock().unwrap();
             let worker = mut_data.drivers.get( &medium.driver_id ).ok_or(MediaError::InvalidHandle)?;
             let (tx, rx) = oneshot::channel();
-            worker.tx.send( ToWorkerMsg::Fetch { res_rx: tx, attachId: medium.id, path: path.as_ref().to_owned(), spec: fileSpec.clone() } ).map_err(|e| MediaError::WorkerBroken(e.to_string()))?;
+            worker.tx.send( ToWorkerMsg::Fetch { res_rx: tx, attachId: medium.id, path: path.as_ref().to_owned(), spec: file_spec.clone() } ).map_err(|e| MediaError::WorkerBroken(e.to_string()))?;
             resRx = Some(rx);
         }
 
         if let Some(rx) = resRx {
-            return rx.await.map_err(|e|MediaError::WorkerBroken(e.to_string()) )?;
+            return match rx.await.map_err(|e|MediaError::WorkerBroken(e.to_string()) )? {
+                Err(ZyppError::Media { source: MediaError::FileNotFound }) if file_spec.optional => Ok( ProvideStatus::NotFound ),
+                res => res
+            };
         }
         Err( MediaError::InvalidHandle.into() )
     }
 
+    /// Lists a directory on the medium, see MediaDriver::list_dir
+    pub async fn list_dir<P: AsRef<Path>> ( &self, medium: &AttachedMedium, path: P ) -> Result<Vec<String>, ZyppError> {
+        let rx = {
+            let mut_data = self.data.lock().unwrap();
+            let worker = mut_data.drivers.get( &medium.driver_id ).ok_or(MediaError::InvalidHandle)?;
+            let (tx, rx) = oneshot::channel();
+            worker.tx.send( ToWorkerMsg::ListDir { res_rx: tx, attachId: medium.id, path: path.as_ref().to_owned() } ).map_err(|e| MediaError::WorkerBroken(e.to_string()))?;
+            rx
+        };
+        rx.await.map_err(|e|MediaError::WorkerBroken(e.to_string()) )?
+    }
+
+    /// The media that are attached right now, for diagnostics
+    pub fn attached_media( &self ) -> Vec<AttachedMediumInfo> {
+        let data = self.data.lock().unwrap();
+        let mut media: Vec<AttachedMediumInfo> = data.attached.values().cloned().collect();
+        media.sort_by_key( |m| (m.driver_id, m.id) );
+        media
+    }
+
     pub fn add_driver( &self, driver: Box<dyn MediaDriver + Send + Sync> ) {
         let mut mut_data = self.data.lock().unwrap();
         mut_data.next_driver_id+=1;
@@ -227,3 +319,113 @@ impl Manager {
         mut_data.drivers.insert( my_id, WorkerHandle::new(driver) );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byte_unit::Byte;
+    use crate::media::spec::FileInfo;
+    use async_trait::async_trait;
+    use std::time::Duration;
+
+    async fn attach_dir( manager: &Manager, dir: &Path ) -> AttachedMedium {
+        let url = Url::from_directory_path( dir ).unwrap();
+        manager.attach( &vec![ url ], &MediaSpec { label: "test".to_owned(), medianr: 1, verify_data_path: None } ).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn file_over_the_download_size_is_not_provided() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write( dir.path().join("big.bin"), [0u8; 100] ).unwrap();
+        let manager = Manager::new();
+        let medium = attach_dir( &manager, dir.path() ).await;
+
+        let spec = FileSpec { downloadSize: Byte::from_bytes( 99 ), ..Default::default() };
+        let res = manager.provide( &medium, "/big.bin", &spec ).await;
+        assert!( matches!( res, Err( ZyppError::Media { source: MediaError::DownloadSizeExceeded(99) } ) ), "{:?}", res );
+
+        let spec = FileSpec { downloadSize: Byte::from_bytes( 100 ), ..Default::default() };
+        assert_eq!( manager.fetch( &medium, "/big.bin", &spec ).await.unwrap(), dir.path().join("big.bin") );
+    }
+
+    #[tokio::test]
+    async fn missing_optional_file_is_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = Manager::new();
+        let medium = attach_dir( &manager, dir.path() ).await;
+
+        let optional = FileSpec { optional: true, ..Default::default() };
+        assert_eq!( manager.provide( &medium, "/missing.txt", &optional ).await.unwrap(), ProvideStatus::NotFound );
+        let res = manager.provide( &medium, "/missing.txt", &FileSpec::default() ).await;
+        assert!( matches!( res, Err( ZyppError::Media { source: MediaError::FileNotFound } ) ), "{:?}", res );
+    }
+
+    #[tokio::test]
+    async fn check_exists_only_does_not_provide_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write( dir.path().join("f.txt"), "hello\n" ).unwrap();
+        let manager = Manager::new();
+        let medium = attach_dir( &manager, dir.path() ).await;
+
+        let spec = FileSpec { checkExistsOnly: true, ..Default::default() };
+        let Ok(ProvideStatus::Exists(FileInfo { size, .. })) = manager.provide( &medium, "/f.txt", &spec ).await else {
+            panic!("The file was not reported as existing");
+        };
+        assert_eq!( size, Some( 6 ) );
+        // fetch wants the file itself
+        assert!( manager.fetch( &medium, "/f.txt", &spec ).await.is_err() );
+    }
+
+    /// Counts the uses of its only medium like the real drivers do and reports every detach
+    /// with the uses that are left
+    struct StubDriver {
+        use_cnt: Mutex<u32>,
+        detached: mpsc::UnboundedSender<(u32, u32)>
+    }
+
+    #[async_trait]
+    impl MediaDriver for StubDriver {
+        fn schemes( &self ) -> Vec<String> {
+            vec!["stub".to_owned()]
+        }
+
+        async fn attach( &self, _urls: Vec<Url>, _spec: MediaSpec ) -> Result<u32, ZyppError> {
+            *self.use_cnt.lock()? += 1;
+            Ok(1)
+        }
+
+        async fn provide( &self, _attach_id: u32, _path: PathBuf, _spec: FileSpec ) -> Result<ProvideStatus, ZyppError> {
+            Err( MediaError::FileNotFound.into() )
+        }
+
+        fn detach( &self, id: u32 ) -> Result<(), ZyppError> {
+            let mut use_cnt = self.use_cnt.lock()?;
+            *use_cnt -= 1;
+            self.detached.send( (id, *use_cnt) ).unwrap();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn last_handle_detaches_the_medium() {
+        let (detached_tx, mut detached) = mpsc::unbounded_channel();
+        let manager = Manager::new();
+        manager.add_driver( Box::new( StubDriver { use_cnt: Mutex::new( 0 ), detached: detached_tx } ) );
+
+        let urls = vec![ Url::parse( "stub:/medium" ).unwrap() ];
+        let spec = MediaSpec { label: "stub".to_owned(), medianr: 1, verify_data_path: None };
+        let first = manager.attach( &urls, &spec ).await.unwrap();
+        let second = manager.attach( &urls, &spec ).await.unwrap();
+        let media = manager.attached_media();
+        assert_eq!( media.len(), 1 );
+        assert_eq!( ( media[0].id, media[0].handles, media[0].label.as_str() ), ( 1, 2, "stub" ) );
+
+        drop( first );
+        assert_eq!( tokio::time::timeout( Duration::from_secs( 5 ), detached.recv() ).await.unwrap(), Some( (1, 1) ) );
+        assert_eq!( manager.attached_media()[0].handles, 1 );
+
+        drop( second );
+        assert_eq!( tokio::time::timeout( Duration::from_secs( 5 ), detached.recv() ).await.unwrap(), Some( (1, 0) ) );
+        assert!( manager.attached_media().is_empty() );
+    }
+}
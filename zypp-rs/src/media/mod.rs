@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::checksum::CheckSum;
+
 pub mod manager;
 pub(crate) mod driver;
 pub mod spec;
@@ -28,6 +30,21 @@ pub enum MediaError {
         #[from]
         source: reqwest::Error
     },
+    #[error("The server replied with HTTP status {0}")]
+    HttpStatus(u16),
+    #[error("Ftp Error - {0}")]
+    FtpError(String),
+    #[error("The operation is not supported by the media driver")]
+    NotSupported,
+    #[error("Checksum mismatch, expected {expected} but got {actual}")]
+    ChecksumMismatch {
+        expected: CheckSum,
+        actual: CheckSum
+    },
+    #[error("The file is larger than its expected download size of {0} bytes")]
+    DownloadSizeExceeded(u128),
+    #[error("Failed to mount {0}")]
+    MountFailed(String),
     #[error("Internal error - {0}")]
     Internal(String)
 }
@@ -1,7 +1,11 @@
-use std::{path::PathBuf, fs::File};
+use std::{path::{Path, PathBuf}, fs::File};
 use byte_unit::Byte;
 use tribool::Tribool::{self, True, False, Indeterminate};
 
+use crate::checksum::{CheckSum, Hasher};
+use crate::error::ZyppError;
+use crate::media::MediaError;
+
 #[derive(Debug, Clone)]
 pub struct MediaSpec {
     pub label: String,
@@ -34,13 +38,16 @@ pub struct FileSpec {
     pub optional : bool,
     pub downloadSize : Byte,
 
-    //zypp::CheckSum  _checksum;
+    /// the drivers verify the file against this checksum while providing it
+    pub checksum : Option<CheckSum>,
 
     pub openSize : Byte,
-    //zypp::CheckSum  _openChecksum;
+    /// checksum of the uncompressed file, not verified by the drivers
+    pub openChecksum : Option<CheckSum>,
 
     pub headerSize: Byte,
-    //zypp::CheckSum  _headerChecksum;
+    /// checksum of the rpm header, not verified by the drivers
+    pub headerChecksum : Option<CheckSum>,
 
     pub deltafile: PathBuf
 }
@@ -51,9 +58,74 @@ impl Default for FileSpec {
             checkExistsOnly: false,
             optional: false,
             downloadSize: Byte::from_bytes(0),
+            checksum: None,
             openSize: Byte::from_bytes(0),
+            openChecksum: None,
             headerSize: Byte::from_bytes(0),
+            headerChecksum: None,
             deltafile: Default::default()
         }
     }
 }
+
+/// What is known about a file on the medium without downloading it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileInfo {
+    pub size: Option<u64>,
+    /// modification time in seconds since the epoch
+    pub mtime: Option<u64>
+}
+
+/// What a media driver provided for a FileSpec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProvideStatus {
+    /// the file was downloaded or is available locally at the path
+    Provided(PathBuf),
+    /// the file exists on the medium, it was not downloaded since checkExistsOnly was set.
+    /// Size and modification time are filled in as far as the medium tells them.
+    Exists(FileInfo),
+    /// the file does not exist on the medium, this is only reported for optional files,
+    /// for all others it is a MediaError::FileNotFound
+    NotFound
+}
+
+impl FileSpec {
+    /// Fails as soon as more than downloadSize bytes were downloaded, a downloadSize of 0 means unlimited
+    pub(crate) fn check_download_size( &self, downloaded: u64 ) -> Result<(), MediaError> {
+        let limit = self.downloadSize.get_bytes();
+        if limit > 0 && downloaded as u128 > limit {
+            return Err( MediaError::DownloadSizeExceeded( limit ) );
+        }
+        Ok(())
+    }
+
+    /// Returns a hasher to calculate the checksum while the file is downloaded,
+    /// if the spec has a checksum
+    pub(crate) fn checksum_hasher( &self ) -> Option<Hasher> {
+        self.checksum.as_ref().map( CheckSum::hasher )
+    }
+
+    /// Compares the checksum calculated by the hasher from checksum_hasher with the expected one
+    pub(crate) fn verify_checksum( &self, hasher: Option<Hasher> ) -> Result<(), MediaError> {
+        match ( &self.checksum, hasher ) {
+            ( Some(expected), Some(hasher) ) => Self::compare_checksum( expected, hasher.finalize() ),
+            _ => Ok(())
+        }
+    }
+
+    /// Verifies the size and checksum of a file that is already on disk, like files of local media
+    pub(crate) fn verify_file( &self, file: &Path ) -> Result<(), ZyppError> {
+        self.check_download_size( file.metadata()?.len() )?;
+        if let Some(expected) = &self.checksum {
+            Self::compare_checksum( expected, CheckSum::of_file( expected.kind(), file )? )?;
+        }
+        Ok(())
+    }
+
+    fn compare_checksum( expected: &CheckSum, actual: CheckSum ) -> Result<(), MediaError> {
+        if actual != *expected {
+            return Err( MediaError::ChecksumMismatch { expected: expected.clone(), actual } );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// A rpm in a plaindir repository, a directory of rpms without any metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaindirEntry {
+    /// path of the rpm, relative to the repository root
+    pub location: String,
+    pub size: Option<u64>,
+    /// modification time in seconds since the epoch
+    pub mtime: Option<u64>,
+}
+
+impl PlaindirEntry {
+    pub fn new( location: &str ) -> Self {
+        Self { location: location.to_owned(), size: None, mtime: None }
+    }
+}
+
+fn is_rpm( path: &Path ) -> bool {
+    path.extension().map_or( false, |e| e == "rpm" )
+}
+
+/// Finds all rpms in a local directory and its subdirectories, hidden directories are
+/// skipped. The entries are sorted by location.
+pub fn scan_dir<P: AsRef<Path>>( dir: P ) -> io::Result<Vec<PlaindirEntry>> {
+    let mut res = Vec::new();
+    scan_dir_into( dir.as_ref(), "", &mut res )?;
+    res.sort_by( |a, b| a.location.cmp( &b.location ) );
+    Ok(res)
+}
+
+fn scan_dir_into( dir: &Path, prefix: &str, res: &mut Vec<PlaindirEntry> ) -> io::Result<()> {
+    for entry in fs::read_dir( dir )? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let location = format!("{}{}", prefix, name);
+        if path.is_dir() {
+            scan_dir_into( &path, &format!("{}/", location), res )?;
+        } else if is_rpm( &path ) && path.is_file() {
+            let meta = entry.metadata()?;
+            res.push( PlaindirEntry {
+                location,
+                size: Some( meta.len() ),
+                mtime: meta.modified().ok()
+                    .and_then( |t| t.duration_since( UNIX_EPOCH ).ok() )
+                    .map( |d| d.as_secs() ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The content of the cookie file in the raw cache of a plaindir repository, one line per rpm.
+/// It changes whenever a rpm is added, removed or, if size and time are known, replaced,
+/// which tells us the .solv file needs to be rebuilt.
+pub fn cookie( entries: &[PlaindirEntry] ) -> String {
+    let mut res = String::new();
+    for entry in entries {
+        res.push_str( &cookie_line( entry ) );
+        res.push('\n');
+    }
+    res
+}
+
+/// The line of a single rpm in the cookie, without the line break
+pub fn cookie_line( entry: &PlaindirEntry ) -> String {
+    let mut res = entry.location.clone();
+    if let Some(size) = entry.size {
+        res.push_str( &format!(" {}", size) );
+    }
+    if let Some(mtime) = entry.mtime {
+        res.push_str( &format!(" {}", mtime) );
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn locations( entries: &[PlaindirEntry] ) -> Vec<&str> {
+        entries.iter().map( |e| e.location.as_str() ).collect()
+    }
+
+    #[test]
+    fn scan_skips_hidden_dirs_and_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for sub in ["x86_64", "noarch/sub", ".hidden", "x86_64/.snapshot"] {
+            fs::create_dir_all( dir.path().join( sub ) ).unwrap();
+        }
+        for file in ["x86_64/foo-1.0-1.x86_64.rpm", "noarch/sub/bar-1.0-1.noarch.rpm", "top.rpm",
+                     ".hidden/hidden-1.0-1.noarch.rpm", "x86_64/.snapshot/old-1.0-1.x86_64.rpm",
+                     ".dotted.rpm", "README", "x86_64/foo.rpm.sig"] {
+            fs::write( dir.path().join( file ), "rpm" ).unwrap();
+        }
+        // a directory named like an rpm is searched, not taken
+        fs::create_dir( dir.path().join( "dir.rpm" ) ).unwrap();
+        fs::write( dir.path().join( "dir.rpm/in-1.0-1.noarch.rpm" ), "rpm" ).unwrap();
+
+        let entries = scan_dir( dir.path() ).unwrap();
+        assert_eq!( locations( &entries ), vec![
+            "dir.rpm/in-1.0-1.noarch.rpm",
+            "noarch/sub/bar-1.0-1.noarch.rpm",
+            "top.rpm",
+            "x86_64/foo-1.0-1.x86_64.rpm",
+        ]);
+        assert!( entries.iter().all( |e| e.size == Some( 3 ) && e.mtime.is_some() ) );
+    }
+
+    #[test]
+    fn cookie_changes_with_size_and_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let rpm = dir.path().join( "foo-1.0-1.noarch.rpm" );
+        let set_mtime = |secs: u64| {
+            fs::File::options().write( true ).open( &rpm ).unwrap()
+                .set_modified( UNIX_EPOCH + Duration::from_secs( secs ) ).unwrap();
+        };
+        fs::write( &rpm, "rpm" ).unwrap();
+        set_mtime( 1701432000 );
+
+        let first = cookie( &scan_dir( dir.path() ).unwrap() );
+        assert_eq!( first, "foo-1.0-1.noarch.rpm 3 1701432000\n" );
+        assert_eq!( cookie( &scan_dir( dir.path() ).unwrap() ), first );
+
+        set_mtime( 1701432001 );
+        let touched = cookie( &scan_dir( dir.path() ).unwrap() );
+        assert_ne!( touched, first );
+
+        fs::write( &rpm, "bigger rpm" ).unwrap();
+        set_mtime( 1701432001 );
+        let replaced = cookie( &scan_dir( dir.path() ).unwrap() );
+        assert_ne!( replaced, touched );
+
+        // without size and time only added and removed rpms are noticed
+        assert_eq!( cookie( &[ PlaindirEntry::new( "foo-1.0-1.noarch.rpm" ) ] ), "foo-1.0-1.noarch.rpm\n" );
+    }
+}
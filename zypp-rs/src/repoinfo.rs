@@ -6,7 +6,11 @@ use std::collections::HashMap;
 use std::string::ToString;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
-use log::warn;
+use log::{debug, warn};
+use std::io;
+
+use crate::inifile::IniFile;
+use crate::repovariables::RepoVariables;
 
 #[derive(Error, Debug)]
 pub enum ParseRepoFileError {
@@ -25,10 +29,13 @@ pub enum Error {
     UnknownRepoType(String),
 
     #[error(transparent)]
-    ParseRepoFileError(#[from] ParseRepoFileError)
+    ParseRepoFileError(#[from] ParseRepoFileError),
+
+    #[error("IO Error - {0}")]
+    Io(#[from] io::Error)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RepoType {
   None,
   RpmMd,
@@ -65,20 +72,114 @@ impl ToString for RepoType {
           RepoType::None => "none".to_owned(),
           RepoType::RpmMd => "rpm-md".to_owned(),
           RepoType::Yast2 => "yast2".to_owned(),
-          RepoType::RpmPlainDir => "NONE".to_owned(),
+          RepoType::RpmPlainDir => "plaindir".to_owned(),
         }
     }
 }
-#[derive(Default, Debug)]
+
+/// Priority used for repositories that do not set one, same as in libzypp
+pub const DEFAULT_PRIORITY: u32 = 99;
+
+#[derive(Debug)]
 pub struct RepoInfo
 {
   pub repo_alias: String,
   pub repo_name: String,
   pub repo_type: RepoType,
+  pub enabled: bool,
+  pub autorefresh: bool,
+  pub priority: u32,
+  pub path: PathBuf,
   pub raw_gpg_check: tribool::Tribool,
+  pub raw_repo_gpg_check: tribool::Tribool,
+  pub raw_pkg_gpg_check: tribool::Tribool,
+  pub gpg_keys: Vec<Url>,
+  /// base urls as written in the repo file, before any variables were replaced.
+  /// This is what gets written back, if it is empty base_urls is used instead.
+  pub raw_base_urls: Vec<String>,
+  /// base urls with all repo variables replaced, see expand_variables
   pub base_urls: Vec<Url>,
+  pub mirrorlist: Option<Url>,
+  pub metalink: Option<Url>,
+  pub keep_packages: bool,
+  /// alias of the service this repo belongs to, empty if it was added manually
+  pub service: String,
+  /// what expand_variables put into base_urls, to tell if code changed them afterwards
+  expanded_base_urls: Option<Vec<Url>>,
   metadata_path: PathBuf,
-  packages_path: PathBuf
+  packages_path: PathBuf,
+  file_path: PathBuf
+}
+
+impl Default for RepoInfo {
+  fn default() -> Self {
+    Self {
+      repo_alias: Default::default(),
+      repo_name: Default::default(),
+      repo_type: Default::default(),
+      enabled: true,
+      autorefresh: false,
+      priority: DEFAULT_PRIORITY,
+      path: PathBuf::from("/"),
+      raw_gpg_check: Tribool::Indeterminate,
+      raw_repo_gpg_check: Tribool::Indeterminate,
+      raw_pkg_gpg_check: Tribool::Indeterminate,
+      gpg_keys: Default::default(),
+      raw_base_urls: Default::default(),
+      base_urls: Default::default(),
+      mirrorlist: None,
+      metalink: None,
+      keep_packages: false,
+      service: Default::default(),
+      expanded_base_urls: None,
+      metadata_path: Default::default(),
+      packages_path: Default::default(),
+      file_path: Default::default()
+    }
+  }
+}
+
+/// Parses a boolean the way libzypp does in str::strToBool, anything that is neither
+/// a known true nor false value is rejected.
+pub(crate) fn parse_bool( key: &str, val: &str ) -> Result<bool, ParseRepoFileError> {
+  match val.trim().to_lowercase().as_str() {
+    "1"|"yes"|"true"|"on"|"+"|"always" => Ok(true),
+    "0"|"no"|"false"|"off"|"-"|"never" => Ok(false),
+    &_ => Err(ParseRepoFileError::InvalidValue { key: key.to_owned(), value: val.to_owned() })
+  }
+}
+
+/// Same as parse_bool, but additionally accepts libzypp's spelling of the indeterminate state
+fn parse_tribool( key: &str, val: &str ) -> Result<Tribool, ParseRepoFileError> {
+  match val.trim().to_lowercase().as_str() {
+    ""|"indeterminate"|"auto" => Ok(Tribool::Indeterminate),
+    v => parse_bool( key, v ).map(Tribool::from)
+  }
+}
+
+pub(crate) fn bool_value( val: bool ) -> Vec<String> {
+  vec![ if val { "1".to_owned() } else { "0".to_owned() } ]
+}
+
+/// The values to store for a tribool key, None leaves the key alone. Indeterminate has no value
+/// of its own, so a key that already says so, like a hand written gpgcheck=auto, is kept as it is
+/// and only a key with a definite value is removed.
+fn tribool_values( doc: &IniFile, section: &str, key: &str, val: Tribool ) -> Option<Vec<String>> {
+  match val {
+    Tribool::True => Some( bool_value(true) ),
+    Tribool::False => Some( bool_value(false) ),
+    Tribool::Indeterminate => {
+      let current = doc.get( section, key ).and_then( |v| v.into_iter().next() ).unwrap_or_default();
+      match parse_tribool( key, &current ) {
+        Ok(Tribool::Indeterminate) => None,
+        _ => Some( Vec::new() )
+      }
+    }
+  }
+}
+
+pub(crate) fn parse_url( key: &str, val: &str ) -> Result<Url, ParseRepoFileError> {
+  Url::from_str(val.trim()).map_err( |_e| ParseRepoFileError::InvalidValue { key: key.to_owned(), value: val.to_owned() } )
 }
 
 impl RepoInfo {
@@ -99,27 +200,49 @@ impl RepoInfo {
       // convenience access to the first value, usually we only need that
       let first_val: &str = values.first().unwrap();
 
-      println!("Found key: {key} and val: {val}");
+      debug!("Found key: {key} and val: {val}");
       match key.to_lowercase().as_str() {
         "type" => info.repo_type = RepoType::from_str(first_val)?,
         "name" => info.repo_name = first_val.to_owned(),
-        "raw_gpg_check" => {
-            info.raw_gpg_check = Tribool::from_str(val.as_str()).map_err(|_e| ParseRepoFileError::InvalidValue { key: key.to_owned(), value: first_val.to_owned() } )?
+        "enabled" => info.enabled = parse_bool( key, first_val )?,
+        "autorefresh" => info.autorefresh = parse_bool( key, first_val )?,
+        "keeppackages" => info.keep_packages = parse_bool( key, first_val )?,
+        "priority" => {
+          let prio = first_val.trim().parse::<u32>().map_err(|_e| ParseRepoFileError::InvalidValue { key: key.to_owned(), value: first_val.to_owned() } )?;
+          // libzypp treats 0 as "use the default"
+          info.priority = if prio == 0 { DEFAULT_PRIORITY } else { prio };
+        },
+        "path" => info.path = PathBuf::from( first_val.trim() ),
+        "gpgcheck" | "raw_gpg_check" => info.raw_gpg_check = parse_tribool( key, first_val )?,
+        "repo_gpgcheck" => info.raw_repo_gpg_check = parse_tribool( key, first_val )?,
+        "pkg_gpgcheck" => info.raw_pkg_gpg_check = parse_tribool( key, first_val )?,
+        "gpgkey" => {
+          for urlstr in val.split_whitespace() {
+            info.gpg_keys.push( parse_url( key, urlstr )? );
+          }
         },
         "baseurl" => {
-          for urlstr in values {
-            info.base_urls.push( Url::from_str(urlstr).map_err( |e| ParseRepoFileError::InvalidValue { key: key.to_owned(), value: val.to_owned() } )? );
+          for urlstr in values.iter().map(|v| v.trim() ).filter(|v| !v.is_empty() ) {
+            info.raw_base_urls.push( urlstr.to_owned() );
+            // urls using variables can only be checked after expand_variables was called
+            if !urlstr.contains('$') {
+              info.base_urls.push( parse_url( key, urlstr )? );
+            }
           }
         },
+        "mirrorlist" => info.mirrorlist = Some( parse_url( key, first_val )? ),
+        "metalink" => info.metalink = Some( parse_url( key, first_val )? ),
+        "service" => info.service = first_val.trim().to_owned(),
         &_ => warn!("Seen unknown key {} with value {}", key, val), //ignore unknown fields but log them
       }
     }
     Ok(info)
   }
 
-  fn probe_cache<P: AsRef<Path>>( cache_path: P ) -> RepoType {
+  /// Detects the repo type from the raw metadata in a local directory
+  pub fn probe_cache<P: AsRef<Path>>( cache_path: P ) -> RepoType {
     let mut rtype = RepoType::None;
-    if !cache_path.as_ref().is_dir() {
+    if cache_path.as_ref().is_dir() {
       if cache_path.as_ref().join("repodata/repomd.xml").is_file() {
         rtype = RepoType::RpmMd;
       }
@@ -136,20 +259,143 @@ impl RepoInfo {
   pub fn read_from_file<P: AsRef<Path>>( file_path: P) -> Result<Vec<RepoInfo>, Error> {
     let mut res: Vec<RepoInfo> = Vec::new();
 
-    let mut config = Ini::new();
+    // aliases are case sensitive
+    let mut config = Ini::new_cs();
     config.set_multiline(true);
     
-    let repo_file = config.load(file_path).map_err(ParseRepoFileError::ParserError)?;
+    let repo_file = config.load(file_path.as_ref()).map_err(ParseRepoFileError::ParserError)?;
 
     for ( sec, prop ) in repo_file.iter()  {
       if sec.len() == 0  {
         continue;
       }
-      res.push( RepoInfo::from_section( &sec, &prop )? );
+      let mut info = RepoInfo::from_section( &sec, &prop )?;
+      info.set_file_path( &file_path );
+      res.push( info );
     }
     Ok(res)
   }
 
+  /// Whether signatures are checked, repos that do not say anything are checked like in libzypp
+  pub fn gpg_check( &self ) -> bool {
+    self.raw_gpg_check != Tribool::False
+  }
+
+  /// Replaces the repo variables in the raw base urls and stores the result in base_urls.
+  /// A RepoInfo built in code only has base_urls, those become the raw urls then.
+  pub fn expand_variables( &mut self, vars: &RepoVariables ) -> Result<(), Error> {
+    if self.raw_base_urls.is_empty() {
+      self.raw_base_urls = self.base_urls.iter().map(Url::to_string).collect();
+    }
+    let mut urls = Vec::with_capacity( self.raw_base_urls.len() );
+    for raw in &self.raw_base_urls {
+      urls.push( parse_url( "baseurl", &vars.expand(raw) )? );
+    }
+    self.expanded_base_urls = Some( urls.clone() );
+    self.base_urls = urls;
+    Ok(())
+  }
+
+  /// base_urls is what code changes, if it was changed after expand_variables the raw urls
+  /// are replaced by it. Raw urls that were never expanded, or failed to expand, are kept.
+  pub fn sync_raw_base_urls( &mut self ) {
+    if self.base_urls.is_empty() {
+      return;
+    }
+    let changed = match &self.expanded_base_urls {
+      Some(expanded) => *expanded != self.base_urls,
+      None => self.raw_base_urls.is_empty()
+    };
+    if changed {
+      self.raw_base_urls = self.base_urls.iter().map(Url::to_string).collect();
+    }
+  }
+
+  /// Stores all our settings in the section named after the alias in the given document.
+  ///
+  /// Keys that already exist are updated in place, so even a value that matches the default
+  /// stays in the file if the user put it there. New keys are only added when they differ
+  /// from the default, and keys without a value are removed. Anything we do not know about is
+  /// left alone.
+  pub(crate) fn store_in( &self, doc: &mut IniFile ) {
+    let alias = self.repo_alias.as_str();
+
+    // raw_gpg_check is accepted as well when reading, files using it keep using it
+    let gpg_check_key = if !doc.has_key( alias, "gpgcheck" ) && doc.has_key( alias, "raw_gpg_check" ) { "raw_gpg_check" } else { "gpgcheck" };
+    let gpg_checks = [
+      ( gpg_check_key, tribool_values( doc, alias, gpg_check_key, self.raw_gpg_check ) ),
+      ( "repo_gpgcheck", tribool_values( doc, alias, "repo_gpgcheck", self.raw_repo_gpg_check ) ),
+      ( "pkg_gpgcheck", tribool_values( doc, alias, "pkg_gpgcheck", self.raw_pkg_gpg_check ) )
+    ];
+
+    let mut put = |key: &str, values: Vec<String>, is_default: bool| {
+      if values.is_empty() {
+        doc.remove_key( alias, key );
+      } else if !is_default || doc.has_key( alias, key ) {
+        doc.set( alias, key, &values );
+      }
+    };
+
+    put( "name", vec![ self.repo_name.clone() ], self.repo_name.is_empty() );
+    put( "enabled", bool_value(self.enabled), false );
+    put( "autorefresh", bool_value(self.autorefresh), false );
+    let base_urls = if self.raw_base_urls.is_empty() {
+      self.base_urls.iter().map(Url::to_string).collect()
+    } else {
+      self.raw_base_urls.clone()
+    };
+    put( "baseurl", base_urls, false );
+    put( "mirrorlist", self.mirrorlist.iter().map(Url::to_string).collect(), false );
+    put( "metalink", self.metalink.iter().map(Url::to_string).collect(), false );
+    put( "path", vec![ self.path.to_string_lossy().to_string() ], self.path == Path::new("/") );
+    match self.repo_type {
+      RepoType::None => put( "type", Vec::new(), true ),
+      _ => put( "type", vec![ self.repo_type.to_string() ], false )
+    }
+    put( "priority", vec![ self.priority.to_string() ], self.priority == DEFAULT_PRIORITY );
+    put( "keeppackages", bool_value(self.keep_packages), !self.keep_packages );
+    for (key, values) in gpg_checks {
+      if let Some(values) = values {
+        put( key, values, false );
+      }
+    }
+    put( "gpgkey", self.gpg_keys.iter().map(Url::to_string).collect(), false );
+    put( "service", if self.service.is_empty() { Vec::new() } else { vec![ self.service.clone() ] }, false );
+  }
+
+  /// Removes the section of this repo from the given .repo file, if it was the
+  /// last section in that file the file itself is deleted.
+  pub fn remove_from_file<P: AsRef<Path>>( &self, file_path: P ) -> Result<(), Error> {
+    let mut doc = IniFile::load( &file_path )?;
+    doc.remove_section( &self.repo_alias );
+    if doc.sections().is_empty() {
+      std::fs::remove_file( file_path )?;
+    } else {
+      doc.save( file_path )?;
+    }
+    Ok(())
+  }
+
+  /// Renders the repo as a libzypp compatible .repo file
+  pub fn to_ini_string( &self ) -> String {
+    let mut doc = IniFile::default();
+    self.store_in( &mut doc );
+    doc.to_string()
+  }
+
+  /// Writes the repo into the given .repo file. If the file exists already only the section
+  /// of this repo is changed, comments, other sections and unknown keys are kept as they are.
+  pub fn write_to_file<P: AsRef<Path>>( &self, file_path: P ) -> Result<(), Error> {
+    let mut doc = if file_path.as_ref().exists() {
+      IniFile::load( &file_path )?
+    } else {
+      IniFile::default()
+    };
+    self.store_in( &mut doc );
+    doc.save( file_path )?;
+    Ok(())
+  }
+
   pub fn set_metadata_path<P: AsRef<Path>>( & mut self, new_path: P ) {
     self.metadata_path = new_path.as_ref().to_path_buf();
   }
@@ -166,4 +412,100 @@ impl RepoInfo {
     &self.packages_path
   }
 
+  /// The .repo file this repo was read from or was last written to
+  pub fn file_path( &self ) -> &PathBuf {
+    &self.file_path
+  }
+
+  pub fn set_file_path<P: AsRef<Path>>( & mut self, new_path: P ) {
+    self.file_path = new_path.as_ref().to_path_buf();
+  }
+
+  /// The alias in a form that can be used as a file or directory name
+  pub fn escaped_alias( &self ) -> String {
+    self.repo_alias.replace( '/', "_" )
+  }
+
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn read_single( content: &str ) -> (tempfile::TempDir, PathBuf, RepoInfo) {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("test.repo");
+    std::fs::write( &file, content ).unwrap();
+    let info = RepoInfo::read_from_file( &file ).unwrap().pop().unwrap();
+    (dir, file, info)
+  }
+
+  #[test]
+  fn expand_variables_keeps_urls_set_in_code() {
+    let url = Url::parse("http://example.com/repo").unwrap();
+    let mut info = RepoInfo { repo_alias: "repo".to_owned(), base_urls: vec![ url.clone() ], ..Default::default() };
+    info.expand_variables( &RepoVariables::new() ).unwrap();
+    assert_eq!( info.base_urls, vec![ url ] );
+    assert_eq!( info.raw_base_urls, vec![ "http://example.com/repo".to_owned() ] );
+  }
+
+  #[test]
+  fn expand_variables_replaces_raw_urls() {
+    let (_dir, _file, mut info) = read_single( "[repo]\nbaseurl=http://example.com/$releasever/repo\n" );
+    assert!( info.base_urls.is_empty() );
+    let mut vars = RepoVariables::new();
+    vars.set_releasever("15.5");
+    info.expand_variables( &vars ).unwrap();
+    assert_eq!( info.base_urls, vec![ Url::parse("http://example.com/15.5/repo").unwrap() ] );
+  }
+
+  #[test]
+  fn changed_base_urls_replace_raw_urls() {
+    let (_dir, file, mut info) = read_single( "[repo]\nbaseurl=http://example.com/$releasever/repo\n" );
+    let mut vars = RepoVariables::new();
+    vars.set_releasever("15.5");
+    info.expand_variables( &vars ).unwrap();
+
+    // unchanged urls keep their variables
+    info.sync_raw_base_urls();
+    assert_eq!( info.raw_base_urls, vec![ "http://example.com/$releasever/repo".to_owned() ] );
+
+    let new_url = Url::parse("http://mirror.example.com/repo").unwrap();
+    info.base_urls = vec![ new_url.clone() ];
+    info.sync_raw_base_urls();
+    info.write_to_file( &file ).unwrap();
+    info.expand_variables( &vars ).unwrap();
+    assert_eq!( info.base_urls, vec![ new_url ] );
+    assert!( std::fs::read_to_string( &file ).unwrap().contains("baseurl=http://mirror.example.com/repo\n") );
+  }
+
+  #[test]
+  fn indeterminate_gpgcheck_is_kept() {
+    let (_dir, file, mut info) = read_single( "[repo]\nname=Repo\nbaseurl=http://example.com/repo\ngpgcheck=auto\n" );
+    assert_eq!( info.raw_gpg_check, Tribool::Indeterminate );
+
+    info.repo_name = "Renamed".to_owned();
+    info.write_to_file( &file ).unwrap();
+    let content = std::fs::read_to_string( &file ).unwrap();
+    assert!( content.contains("name=Renamed\n") );
+    assert!( content.contains("gpgcheck=auto\n") );
+  }
+
+  #[test]
+  fn definite_gpgcheck_is_removed_when_indeterminate() {
+    let (_dir, file, mut info) = read_single( "[repo]\nbaseurl=http://example.com/repo\ngpgcheck=0\n" );
+    info.raw_gpg_check = Tribool::Indeterminate;
+    info.write_to_file( &file ).unwrap();
+    assert!( !std::fs::read_to_string( &file ).unwrap().contains("gpgcheck") );
+  }
+
+  #[test]
+  fn raw_gpg_check_key_is_updated() {
+    let (_dir, file, mut info) = read_single( "[repo]\nbaseurl=http://example.com/repo\nraw_gpg_check=0\n" );
+    info.raw_gpg_check = Tribool::True;
+    info.write_to_file( &file ).unwrap();
+    let content = std::fs::read_to_string( &file ).unwrap();
+    assert!( content.contains("raw_gpg_check=1\n") );
+    assert!( !content.contains("\ngpgcheck") );
+  }
 }
@@ -1,13 +1,92 @@
-use crate::repoinfo::RepoInfo;
-use std::path::Path;
+use crate::checksum::{CheckSum, CheckSumType, Hasher};
+use crate::error::ZyppError;
+use crate::inifile::IniFile;
+use crate::media::MediaError;
+use crate::media::manager::{AttachedMedium, Manager};
+use crate::media::spec::{FileSpec, MediaSpec, ProvideStatus};
+use crate::repoinfo::{RepoInfo, RepoType};
+use crate::repomd::{RepoMd, RepoMdData, RepoMdError};
+use crate::plaindir::{self, PlaindirEntry};
+use crate::repovariables::RepoVariables;
+use crate::solv::pool::Pool;
+use crate::solv::repository::Repository;
+use crate::susetags::{ContentFile, SusetagsError, read_patterns_list};
+use crate::serviceinfo::{RepoIndex, RepoIndexEntry, ServiceInfo, ServiceType};
+use std::collections::HashSet;
+use std::path::{Component, Path};
 use std::path::PathBuf;
+use byte_unit::Byte;
 use log::{info, warn};
-use tribool::Tribool;
+use url::Url;
+use thiserror::Error;
 use std::fs;
+use std::os::unix::fs::MetadataExt;
+use tempfile::{NamedTempFile, TempDir};
+
+/// file names inside the solv cache directory of a repository, plaindir repositories
+/// keep a cookie file in their raw cache as well
+const SOLV_FILE: &str = "solv";
+const COOKIE_FILE: &str = "cookie";
+
+/// Name of the repository holding the installed packages
+pub const SYSTEM_REPO_ALIAS: &str = "@System";
+
+/// Possible locations of the rpm database, relative to the root directory. The first
+/// one that exists is used, like librpm the sysimage location is preferred.
+const RPMDB_FILES: [&str; 6] = [
+    "usr/lib/sysimage/rpm/rpmdb.sqlite",
+    "usr/lib/sysimage/rpm/Packages.db",
+    "usr/lib/sysimage/rpm/Packages",
+    "var/lib/rpm/rpmdb.sqlite",
+    "var/lib/rpm/Packages.db",
+    "var/lib/rpm/Packages",
+];
+
+#[derive(Error, Debug)]
+pub enum RepoManagerError {
+    #[error("A repository with alias {0} exists already")]
+    AliasExists(String),
+    #[error("The alias {0} is not valid")]
+    InvalidAlias(String),
+    #[error("There is no repository with alias {0}")]
+    UnknownRepo(String),
+    #[error("Repository {0} has no base url")]
+    NoBaseUrl(String),
+    #[error("Metadata file {file} has size {actual}, expected {expected}")]
+    FileSizeMismatch {
+        file: String,
+        expected: u64,
+        actual: u64
+    },
+    #[error("Metadata location {0} points outside of the repository")]
+    InvalidLocation(String),
+    #[error("There is no metadata for repository {0}, it needs to be refreshed first")]
+    NotCached(String),
+    #[error("Could not detect the type of repository {0}")]
+    ProbeFailed(String),
+    #[error("There is no service with alias {0}")]
+    UnknownService(String),
+    #[error("Service {0} has no url")]
+    NoServiceUrl(String),
+    #[error("Refreshing services of type {0} is not supported")]
+    UnsupportedServiceType(String),
+}
+
+/// Result of refreshing the metadata of one repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshStatus {
+    /// new metadata was downloaded into the raw cache
+    Refreshed,
+    /// the cached metadata is still the same as on the server
+    UpToDate,
+    /// the repository is disabled or has a type that can not be refreshed
+    Skipped,
+}
 
 
 #[derive(Debug)]
 pub struct RepoManagerOptions {
+    pub root_dir: PathBuf,
     pub repo_cache_path: PathBuf,
     pub repo_raw_cache_path: PathBuf,
     pub repo_solv_cache_path: PathBuf,
@@ -15,6 +94,9 @@ pub struct RepoManagerOptions {
     pub known_repos_path: PathBuf,
     pub known_services_path: PathBuf,
     pub plugins_path: PathBuf,
+    /// directory with custom repo variables, one file per variable
+    pub vars_path: PathBuf,
+    /// detect the type of repositories that are added without one, see RepoManager::probe_and_add_repository
     pub probe: bool,
 
     /**
@@ -37,9 +119,11 @@ impl RepoManagerOptions {
             known_repos_path: config_path.join("repos.d"),
             known_services_path: config_path.join("services.d"),
             plugins_path: sys_root.as_ref().join("usr/lib/zypp/plugins"),
+            vars_path: config_path.join("vars.d"),
             probe: false,
             services_target_distro: Default::default(),
             repo_cache_path: repo_cache_path,
+            root_dir: sys_root.as_ref().to_path_buf(),
         }
     }
 }
@@ -48,15 +132,20 @@ impl RepoManagerOptions {
 pub struct RepoManager {
     options: RepoManagerOptions,
     pub repositories: Vec<RepoInfo>,
+    pub services: Vec<ServiceInfo>,
+    pub variables: RepoVariables,
 }
 
 impl RepoManager {
 
     pub fn new(options: RepoManagerOptions) -> Self {
         info!("Loading known repositories.");
+        let variables = RepoVariables::for_target( &options.root_dir, &options.vars_path );
         let mut s = Self {
             options: options,
             repositories: Default::default(),
+            services: Default::default(),
+            variables: variables,
         };
 
         if s.options.known_repos_path.exists() {
@@ -77,16 +166,977 @@ impl RepoManager {
                 ;
 
                 for mut rInfo in infos {
-                    rInfo.raw_gpg_check = Tribool::True;
+                    if let Err(e) = rInfo.expand_variables( &s.variables ) {
+                        warn!("Failed to expand variables for repo {}. {}", rInfo.repo_alias, e );
+                    }
+                    s.set_cache_paths( &mut rInfo );
 
                     s.repositories.push( rInfo );
                 }
             }
         }
+
+        info!("Loading known services.");
+        if s.options.known_services_path.exists() {
+            match fs::read_dir( &s.options.known_services_path ) {
+                Err(e) => warn!("Failed to read directory: {}. {} ", s.options.known_services_path.to_string_lossy(), e ),
+                Ok(entries) => {
+                    let infos = entries
+                        .filter_map( |e| e.ok() )
+                        .filter( |e| e.metadata().map_or_else(|_|false, |e| e.is_file() ) )
+                        .filter( |e| e.path().extension().map_or( false, |ext| ext == "service" ) )
+                        .map( |e| ServiceInfo::read_from_file(e.path()).unwrap_or_else(|e| { warn!( "Failed to read service file. {}", e); Vec::<ServiceInfo>::new() }))
+                        .flatten();
+
+                    for mut sInfo in infos {
+                        if let Err(e) = sInfo.expand_variables( &s.variables ) {
+                            warn!("Failed to expand variables for service {}. {}", sInfo.service_alias, e );
+                        }
+                        s.services.push( sInfo );
+                    }
+                }
+            }
+        }
         return s;
     }
 
-    pub async fn refreshMetadata( repos: &Vec<RepoInfo> ) {
+    pub fn raw_cache_path( &self, info: &RepoInfo ) -> PathBuf {
+        self.options.repo_raw_cache_path.join( info.escaped_alias() )
+    }
+
+    pub fn solv_cache_path( &self, info: &RepoInfo ) -> PathBuf {
+        self.options.repo_solv_cache_path.join( info.escaped_alias() )
+    }
+
+    pub fn packages_cache_path( &self, info: &RepoInfo ) -> PathBuf {
+        self.options.repo_packages_cache_path.join( info.escaped_alias() )
+    }
+
+    fn set_cache_paths( &self, info: &mut RepoInfo ) {
+        info.set_metadata_path( self.raw_cache_path( info ) );
+        info.set_packages_path( self.packages_cache_path( info ) );
+    }
+
+    pub fn get_repository( &self, alias: &str ) -> Option<&RepoInfo> {
+        self.repositories.iter().find( |r| r.repo_alias == alias )
+    }
+
+    fn check_alias( &self, alias: &str ) -> Result<(), RepoManagerError> {
+        // same rules as in libzypp, aliases are used as file names
+        if alias.is_empty() || alias.starts_with('.') || alias.contains('/') {
+            return Err( RepoManagerError::InvalidAlias( alias.to_owned() ) );
+        }
+        if self.get_repository( alias ).is_some() {
+            return Err( RepoManagerError::AliasExists( alias.to_owned() ) );
+        }
+        Ok(())
+    }
+
+    /// Picks a file name for a new repo in known_repos_path, based on the alias
+    fn new_repo_file_path( &self, info: &RepoInfo ) -> PathBuf {
+        let base = info.escaped_alias();
+        let mut path = self.options.known_repos_path.join( format!("{}.repo", base) );
+        let mut cnt = 1;
+        while path.exists() {
+            path = self.options.known_repos_path.join( format!("{}_{}.repo", base, cnt) );
+            cnt += 1;
+        }
+        path
+    }
+
+    /// Removes all cached data of the given repository
+    fn clean_cache( &self, info: &RepoInfo ) -> Result<(), ZyppError> {
+        for dir in [ self.raw_cache_path( info ), self.solv_cache_path( info ), self.packages_cache_path( info ) ] {
+            if dir.exists() {
+                info!("Removing cache directory {}", dir.to_string_lossy() );
+                fs::remove_dir_all( &dir )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a new repository, writing it into its own file in known_repos_path
+    pub fn add_repository( &mut self, mut info: RepoInfo ) -> Result<&RepoInfo, ZyppError> {
+        self.check_alias( &info.repo_alias )?;
+
+        fs::create_dir_all( &self.options.known_repos_path )?;
+        let file_path = self.new_repo_file_path( &info );
+        info.sync_raw_base_urls();
+        info.write_to_file( &file_path )?;
+        info.set_file_path( file_path );
+
+        if let Err(e) = info.expand_variables( &self.variables ) {
+            warn!("Failed to expand variables for repo {}. {}", info.repo_alias, e );
+        }
+        self.set_cache_paths( &mut info );
+
+        // make sure we do not pick up stale data from a repo that had the same alias before
+        self.clean_cache( &info )?;
+
+        info!("Added repository {}", info.repo_alias );
+        self.repositories.push( info );
+        Ok( self.repositories.last().unwrap() )
+    }
+
+    /// Removes a repository from its repo file and deletes all of its cached data
+    pub fn remove_repository( &mut self, alias: &str ) -> Result<RepoInfo, ZyppError> {
+        let idx = self.repositories.iter().position( |r| r.repo_alias == alias ).ok_or( RepoManagerError::UnknownRepo(alias.to_owned()) )?;
+
+        let info = &self.repositories[idx];
+        if info.file_path().exists() {
+            info.remove_from_file( info.file_path() )?;
+        }
+        self.clean_cache( info )?;
+
+        info!("Removed repository {}", alias );
+        Ok( self.repositories.remove( idx ) )
+    }
+
+    /// Replaces the repository known as alias with new_info, the changes are written to the
+    /// file the repository was read from. If the alias changes the cached data is moved as well.
+    pub fn modify_repository( &mut self, alias: &str, mut new_info: RepoInfo ) -> Result<&RepoInfo, ZyppError> {
+        let idx = self.repositories.iter().position( |r| r.repo_alias == alias ).ok_or( RepoManagerError::UnknownRepo(alias.to_owned()) )?;
+        if new_info.repo_alias != alias {
+            self.check_alias( &new_info.repo_alias )?;
+        }
+
+        let file_path = if self.repositories[idx].file_path().as_os_str().is_empty() {
+            fs::create_dir_all( &self.options.known_repos_path )?;
+            self.new_repo_file_path( &new_info )
+        } else {
+            self.repositories[idx].file_path().clone()
+        };
+        // renamed in place, so the comments and unknown keys of the section are kept
+        if new_info.repo_alias != alias && file_path.exists() {
+            let mut doc = IniFile::load( &file_path )?;
+            doc.rename_section( alias, &new_info.repo_alias );
+            doc.save( &file_path )?;
+        }
+        new_info.sync_raw_base_urls();
+        new_info.write_to_file( &file_path )?;
+        new_info.set_file_path( file_path );
+
+        if let Err(e) = new_info.expand_variables( &self.variables ) {
+            warn!("Failed to expand variables for repo {}. {}", new_info.repo_alias, e );
+        }
+        self.set_cache_paths( &mut new_info );
+
+        if new_info.repo_alias != alias {
+            let old_info = &self.repositories[idx];
+            for (old_dir, new_dir) in [
+                ( self.raw_cache_path( old_info ), self.raw_cache_path( &new_info ) ),
+                ( self.solv_cache_path( old_info ), self.solv_cache_path( &new_info ) ),
+                ( self.packages_cache_path( old_info ), self.packages_cache_path( &new_info ) ) ] {
+                if old_dir.exists() {
+                    if new_dir.exists() {
+                        fs::remove_dir_all( &new_dir )?;
+                    }
+                    fs::rename( &old_dir, &new_dir )?;
+                }
+            }
+            info!("Renamed repository {} to {}", alias, new_info.repo_alias );
+        }
+
+        self.repositories[idx] = new_info;
+        Ok( &self.repositories[idx] )
+    }
+
+    /// Downloads the metadata of the given repositories into repo_raw_cache_path, returns the
+    /// result for each of them together with the alias. Downloads are skipped if the index
+    /// file of a repository did not change since the last refresh. Repositories without a
+    /// type are probed first and the detected type is stored in their repo file.
+    pub async fn refreshMetadata( &mut self, media: &Manager, aliases: &[&str] ) -> Vec<(String, Result<RefreshStatus, ZyppError>)> {
+        let mut res = Vec::with_capacity( aliases.len() );
+        for alias in aliases {
+            let status = self.refresh_repository( media, alias ).await;
+            if let Err(e) = &status {
+                warn!("Failed to refresh repository {}. {}", alias, e );
+            }
+            res.push( (alias.to_string(), status) );
+        }
+        res
+    }
+
+    async fn refresh_repository( &mut self, media: &Manager, alias: &str ) -> Result<RefreshStatus, ZyppError> {
+        let idx = self.repositories.iter().position( |r| r.repo_alias == alias ).ok_or( RepoManagerError::UnknownRepo(alias.to_owned()) )?;
+        if !self.repositories[idx].enabled {
+            return Ok( RefreshStatus::Skipped );
+        }
+
+        if self.repositories[idx].repo_type == RepoType::None {
+            let detected = self.probe( media, &self.repositories[idx] ).await?;
+            if detected == RepoType::None {
+                return Err( RepoManagerError::ProbeFailed( alias.to_owned() ).into() );
+            }
+            self.set_repo_type( idx, detected )?;
+        }
+
+        let repo = &self.repositories[idx];
+        match repo.repo_type {
+            RepoType::RpmMd => self.refresh_rpmmd( media, repo ).await,
+            RepoType::Yast2 => self.refresh_susetags( media, repo ).await,
+            RepoType::RpmPlainDir => self.refresh_plaindir( media, repo ).await,
+            _ => {
+                warn!("Refreshing repositories of type {} is not supported, skipping {}", repo.repo_type.to_string(), repo.repo_alias );
+                Ok( RefreshStatus::Skipped )
+            }
+        }
+    }
+
+    /// Stores a detected repo type, in memory and in the repo file
+    fn set_repo_type( &mut self, idx: usize, repo_type: RepoType ) -> Result<(), ZyppError> {
+        let info = &mut self.repositories[idx];
+        info!("Detected type {} for repository {}", repo_type.to_string(), info.repo_alias );
+        info.repo_type = repo_type;
+        if !info.file_path().as_os_str().is_empty() {
+            info.write_to_file( info.file_path() )?;
+        }
+        Ok(())
+    }
+
+    /// The directory of a repository on a local medium, None for remote repositories.
+    /// Like the local media driver this decodes the url, so "%20" becomes a space.
+    fn local_repo_dir( info: &RepoInfo ) -> Option<PathBuf> {
+        info.base_urls.first()
+            .filter( |u| u.scheme() == "dir" || u.scheme() == "file" )
+            .and_then( |u| u.to_file_path().ok() )
+            .map( |dir| dir.join( info.path.strip_prefix("/").unwrap_or( &info.path ) ) )
+    }
+
+    /// Checks if there are rpms in the directory or in one of its direct subdirectories,
+    /// which is where they end up in a directory sorted by architecture.
+    fn has_rpms( dir: &Path ) -> bool {
+        let is_rpm = |p: &Path| p.is_file() && p.extension().map_or( false, |e| e == "rpm" );
+        let Ok(entries) = fs::read_dir( dir ) else {
+            return false;
+        };
+        entries.filter_map( |e| e.ok() ).map( |e| e.path() ).any( |p| {
+            is_rpm( &p ) || ( p.is_dir() && fs::read_dir( &p ).map_or( false, |sub| sub.filter_map( |e| e.ok() ).any( |e| is_rpm( &e.path() ) ) ) )
+        })
+    }
+
+    /// Detects the type of a repository by looking at the files its medium provides.
+    /// Returns RepoType::None if nothing known was found.
+    pub async fn probe( &self, media: &Manager, info: &RepoInfo ) -> Result<RepoType, ZyppError> {
+        if info.base_urls.is_empty() {
+            return Err( RepoManagerError::NoBaseUrl( info.repo_alias.clone() ).into() );
+        }
+
+        // local directories can be checked directly
+        if let Some(dir) = Self::local_repo_dir( info ) {
+            let rtype = RepoInfo::probe_cache( &dir );
+            if rtype != RepoType::None {
+                return Ok(rtype);
+            }
+            return Ok( if Self::has_rpms( &dir ) { RepoType::RpmPlainDir } else { RepoType::None } );
+        }
+
+        let medium = media.attach( &info.base_urls, &MediaSpec { label: info.repo_name.clone(), medianr: 1, verify_data_path: None } ).await?;
+        let optional = FileSpec { optional: true, checkExistsOnly: true, ..Default::default() };
+        for (file, rtype) in [ ("repodata/repomd.xml", RepoType::RpmMd), ("content", RepoType::Yast2) ] {
+            match media.provide( &medium, info.path.join(file), &optional ).await? {
+                ProvideStatus::NotFound => continue,
+                ProvideStatus::Exists(_) | ProvideStatus::Provided(_) => return Ok(rtype)
+            }
+        }
+
+        // no metadata, so it can only be a plain rpm directory. Media that can not be
+        // listed are not probed any further.
+        match Self::list_remote_rpms( media, &medium, &info.path ).await {
+            Ok(rpms) if !rpms.is_empty() => Ok( RepoType::RpmPlainDir ),
+            Ok(_) => Ok( RepoType::None ),
+            Err(ZyppError::Media { source: MediaError::NotSupported | MediaError::FileNotFound }) => Ok( RepoType::None ),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Like add_repository, but if the repository has no type and probing is enabled
+    /// in the options, the type is detected before the repository is added.
+    pub async fn probe_and_add_repository( &mut self, media: &Manager, mut info: RepoInfo ) -> Result<&RepoInfo, ZyppError> {
+        if self.options.probe && info.repo_type == RepoType::None {
+            if let Err(e) = info.expand_variables( &self.variables ) {
+                warn!("Failed to expand variables for repo {}. {}", info.repo_alias, e );
+            }
+            info.repo_type = self.probe( media, &info ).await?;
+            if info.repo_type == RepoType::None {
+                return Err( RepoManagerError::ProbeFailed( info.repo_alias.clone() ).into() );
+            }
+        }
+        self.add_repository( info )
+    }
+
+    async fn refresh_rpmmd( &self, media: &Manager, repo: &RepoInfo ) -> Result<RefreshStatus, ZyppError> {
+        if repo.base_urls.is_empty() {
+            return Err( RepoManagerError::NoBaseUrl( repo.repo_alias.clone() ).into() );
+        }
+
+        let medium = media.attach( &repo.base_urls, &MediaSpec { label: repo.repo_name.clone(), medianr: 1, verify_data_path: None } ).await?;
+        let new_repomd = media.fetch( &medium, repo.path.join("repodata/repomd.xml"), &FileSpec::default() ).await?;
+
+        let raw_cache = self.raw_cache_path( repo );
+        let cached_repomd = raw_cache.join("repodata/repomd.xml");
+        if cached_repomd.is_file() && fs::read( &cached_repomd )? == fs::read( &new_repomd )? {
+            info!("Metadata of repository {} is up to date", repo.repo_alias );
+            return Ok( RefreshStatus::UpToDate );
+        }
+
+        let repomd = RepoMd::read_from_file( &new_repomd )?;
+
+        let staging = self.staging_dir( repo )?;
+
+        // the sqlite and zchunk variants only duplicate what we have already
+        for data in repomd.data.iter().filter( |d| !d.data_type.ends_with("_db") && !d.data_type.ends_with("_zck") ) {
+            Self::check_location( &data.location )?;
+
+            info!("Downloading {} for repository {}", data.location, repo.repo_alias );
+            let spec = FileSpec {
+                downloadSize: Byte::from_bytes( data.size.unwrap_or(0) as u128 ),
+                checksum: data.checksum.clone(),
+                ..Default::default()
+            };
+            let file = media.fetch( &medium, repo.path.join( &data.location ), &spec ).await?;
+            Self::check_metadata_file( &file, data )?;
+            Self::copy_into( &file, staging.path().join( &data.location ) )?;
+        }
+        Self::copy_into( &new_repomd, staging.path().join("repodata/repomd.xml") )?;
+        Self::replace_raw_cache( staging, &raw_cache )?;
+
+        info!("Refreshed metadata of repository {}", repo.repo_alias );
+        Ok( RefreshStatus::Refreshed )
+    }
+
+    async fn refresh_susetags( &self, media: &Manager, repo: &RepoInfo ) -> Result<RefreshStatus, ZyppError> {
+        if repo.base_urls.is_empty() {
+            return Err( RepoManagerError::NoBaseUrl( repo.repo_alias.clone() ).into() );
+        }
+
+        let medium = media.attach( &repo.base_urls, &MediaSpec { label: repo.repo_name.clone(), medianr: 1, verify_data_path: None } ).await?;
+        let new_content = media.fetch( &medium, repo.path.join("content"), &FileSpec::default() ).await?;
+
+        let raw_cache = self.raw_cache_path( repo );
+        let cached_content = raw_cache.join("content");
+        if cached_content.is_file() && fs::read( &cached_content )? == fs::read( &new_content )? {
+            info!("Metadata of repository {} is up to date", repo.repo_alias );
+            return Ok( RefreshStatus::UpToDate );
+        }
+
+        let content = ContentFile::read_from_file( &new_content )?;
+        Self::check_location( &content.descr_dir )?;
+        let staging = self.staging_dir( repo )?;
+        let descr_dir = staging.path().join( &content.descr_dir );
+
+        // packages is the only file every susetags repository needs, the english
+        // translations and the patterns are downloaded if the repository has them
+        let mut wanted = vec![ "packages".to_owned() ];
+        for name in [ "packages.en", "patterns" ] {
+            if content.find_meta( name ).is_some() {
+                wanted.push( name.to_owned() );
+            }
+        }
+        for name in wanted {
+            self.fetch_susetags_file( media, &medium, repo, &content, &name, &descr_dir ).await?;
+        }
+
+        if let Some(patterns) = content.find_meta("patterns") {
+            for pattern in read_patterns_list( descr_dir.join( &patterns.file ) )? {
+                self.fetch_susetags_file( media, &medium, repo, &content, &pattern, &descr_dir ).await?;
+            }
+        }
+
+        Self::copy_into( &new_content, staging.path().join("content") )?;
+        Self::replace_raw_cache( staging, &raw_cache )?;
+
+        info!("Refreshed metadata of repository {}", repo.repo_alias );
+        Ok( RefreshStatus::Refreshed )
+    }
+
+    /// Downloads a file from the description directory of a susetags repository into target_dir,
+    /// the media drivers verify the checksum from the content file
+    async fn fetch_susetags_file( &self, media: &Manager, medium: &AttachedMedium, repo: &RepoInfo, content: &ContentFile, name: &str, target_dir: &Path ) -> Result<(), ZyppError> {
+        let entry = content.find_meta( name ).ok_or( SusetagsError::Invalid( format!("{} is not listed in the content file", name) ) )?;
+        Self::check_location( &entry.file )?;
+
+        info!("Downloading {} for repository {}", entry.file, repo.repo_alias );
+        let spec = FileSpec { checksum: Some( entry.checksum.clone() ), ..Default::default() };
+        let file = media.fetch( medium, repo.path.join( &content.descr_dir ).join( &entry.file ), &spec ).await?;
+        Self::copy_into( &file, target_dir.join( &entry.file ) )
+    }
+
+    /// Plaindir repositories have no metadata, the raw cache only holds the cookie file listing
+    /// the rpms. Rpms on remote media are downloaded into the raw cache as well, local ones
+    /// are read from their directory when the cache is built.
+    async fn refresh_plaindir( &self, media: &Manager, repo: &RepoInfo ) -> Result<RefreshStatus, ZyppError> {
+        let raw_cache = self.raw_cache_path( repo );
+        let cached_cookie = fs::read_to_string( raw_cache.join( COOKIE_FILE ) ).ok();
+
+        if let Some(dir) = Self::local_repo_dir( repo ) {
+            let cookie = plaindir::cookie( &plaindir::scan_dir( &dir )? );
+            if cached_cookie.as_ref() == Some( &cookie ) {
+                info!("Metadata of repository {} is up to date", repo.repo_alias );
+                return Ok( RefreshStatus::UpToDate );
+            }
+            fs::create_dir_all( &raw_cache )?;
+            fs::write( raw_cache.join( COOKIE_FILE ), cookie )?;
+            info!("Refreshed metadata of repository {}", repo.repo_alias );
+            return Ok( RefreshStatus::Refreshed );
+        }
+
+        if repo.base_urls.is_empty() {
+            return Err( RepoManagerError::NoBaseUrl( repo.repo_alias.clone() ).into() );
+        }
+        let medium = media.attach( &repo.base_urls, &MediaSpec { label: repo.repo_name.clone(), medianr: 1, verify_data_path: None } ).await?;
+        // size and modification time tell us if a rpm was replaced by one with the same name
+        let exists_only = FileSpec { checkExistsOnly: true, ..Default::default() };
+        let mut entries = Vec::new();
+        for location in Self::list_remote_rpms( media, &medium, &repo.path ).await? {
+            Self::check_location( &location )?;
+            let mut entry = PlaindirEntry::new( &location );
+            if let ProvideStatus::Exists(info) = media.provide( &medium, repo.path.join( &location ), &exists_only ).await? {
+                entry.size = info.size;
+                entry.mtime = info.mtime;
+            }
+            entries.push( entry );
+        }
+        let cookie = plaindir::cookie( &entries );
+        if cached_cookie.as_ref() == Some( &cookie ) {
+            info!("Metadata of repository {} is up to date", repo.repo_alias );
+            return Ok( RefreshStatus::UpToDate );
+        }
+
+        let cached_lines: HashSet<&str> = cached_cookie.as_deref().unwrap_or_default().lines().collect();
+        let staging = self.staging_dir( repo )?;
+        for entry in entries.iter() {
+            // rpms we have already are only reused if the medium tells us they did not change
+            let cached = raw_cache.join( &entry.location );
+            let unchanged = entry.size.is_some()
+                && cached_lines.contains( plaindir::cookie_line( entry ).as_str() )
+                && fs::metadata( &cached ).is_ok_and( |m| m.is_file() && Some( m.len() ) == entry.size );
+            if unchanged {
+                Self::copy_into( &cached, staging.path().join( &entry.location ) )?;
+                continue;
+            }
+            info!("Downloading {} for repository {}", entry.location, repo.repo_alias );
+            let spec = FileSpec { downloadSize: Byte::from_bytes( entry.size.unwrap_or(0) as u128 ), ..Default::default() };
+            let file = media.fetch( &medium, repo.path.join( &entry.location ), &spec ).await?;
+            Self::copy_into( &file, staging.path().join( &entry.location ) )?;
+        }
+        fs::write( staging.path().join( COOKIE_FILE ), cookie )?;
+        Self::replace_raw_cache( staging, &raw_cache )?;
+
+        info!("Refreshed metadata of repository {}", repo.repo_alias );
+        Ok( RefreshStatus::Refreshed )
+    }
+
+    /// Finds the rpms of a remote plaindir repository by walking its directory listings
+    async fn list_remote_rpms( media: &Manager, medium: &AttachedMedium, root: &Path ) -> Result<Vec<String>, ZyppError> {
+        let mut rpms = Vec::new();
+        let mut dirs = vec![ String::new() ];
+        while let Some(dir) = dirs.pop() {
+            for entry in media.list_dir( medium, root.join( &dir ) ).await? {
+                if let Some(sub_dir) = entry.strip_suffix('/') {
+                    dirs.push( format!("{}{}/", dir, sub_dir) );
+                } else if entry.ends_with(".rpm") {
+                    rpms.push( format!("{}{}", dir, entry) );
+                }
+            }
+        }
+        rpms.sort();
+        Ok(rpms)
+    }
+
+    /// Metadata is downloaded into a staging directory first, so a failed
+    /// refresh does not leave us with a half updated cache
+    fn staging_dir( &self, repo: &RepoInfo ) -> Result<TempDir, ZyppError> {
+        fs::create_dir_all( &self.options.repo_raw_cache_path )?;
+        Ok( tempfile::Builder::new()
+            .prefix( &format!(".{}", repo.escaped_alias()) )
+            .tempdir_in( &self.options.repo_raw_cache_path )? )
+    }
+
+    /// Moves a completely downloaded staging directory in place of the raw cache
+    fn replace_raw_cache( staging: TempDir, raw_cache: &Path ) -> Result<(), ZyppError> {
+        if raw_cache.exists() {
+            fs::remove_dir_all( raw_cache )?;
+        }
+        fs::rename( staging.path(), raw_cache )?;
+        Ok(())
+    }
+
+    /// Paths from metadata files must stay inside the repository
+    fn check_location( location: &str ) -> Result<(), ZyppError> {
+        if Path::new( location ).components().any( |c| !matches!( c, Component::Normal(_) | Component::CurDir ) ) {
+            return Err( RepoManagerError::InvalidLocation( location.to_owned() ).into() );
+        }
+        Ok(())
+    }
+
+    /// Checks a downloaded metadata file against the size given in repomd.xml,
+    /// the checksum is already verified by the media drivers
+    fn check_metadata_file( file: &Path, data: &RepoMdData ) -> Result<(), ZyppError> {
+        if let Some(expected) = data.size {
+            let actual = fs::metadata( file )?.len();
+            if actual != expected {
+                return Err( RepoManagerError::FileSizeMismatch { file: data.location.clone(), expected, actual }.into() );
+            }
+        }
+        Ok(())
+    }
+
+    fn copy_into( from: &Path, to: PathBuf ) -> Result<(), ZyppError> {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all( parent )?;
+        }
+        fs::copy( from, &to )?;
+        Ok(())
+    }
+
+    /// The type of a repository, repositories without a type are detected from their raw metadata
+    fn cached_repo_type( &self, info: &RepoInfo ) -> RepoType {
+        match info.repo_type {
+            RepoType::None => RepoInfo::probe_cache( self.raw_cache_path( info ) ),
+            rtype => rtype
+        }
+    }
+
+    /// The cookie identifies the raw metadata a .solv file was built from, it is
+    /// the checksum of the index file of the repository.
+    fn raw_metadata_cookie( &self, info: &RepoInfo ) -> Result<String, ZyppError> {
+        let raw_cache = self.raw_cache_path( info );
+        let index_file = match self.cached_repo_type( info ) {
+            RepoType::RpmMd => raw_cache.join("repodata/repomd.xml"),
+            RepoType::Yast2 => raw_cache.join("content"),
+            RepoType::RpmPlainDir => raw_cache.join( COOKIE_FILE ),
+            RepoType::None => return Err( RepoManagerError::NotCached( info.repo_alias.clone() ).into() )
+        };
+        if !index_file.is_file() {
+            return Err( RepoManagerError::NotCached( info.repo_alias.clone() ).into() );
+        }
+        Ok( CheckSum::of_file( CheckSumType::Sha256, &index_file )?.to_string() )
+    }
+
+    /// Builds the .solv file of a repository from its raw metadata. Nothing is done if the
+    /// cookie stored next to the .solv file shows that it was built from the same metadata
+    /// already. Returns true if the cache was (re)built.
+    pub fn build_cache( &self, info: &RepoInfo ) -> Result<bool, ZyppError> {
+        let cookie = self.raw_metadata_cookie( info )?;
+        let solv_dir = self.solv_cache_path( info );
+        let solv_file = solv_dir.join( SOLV_FILE );
+        let cookie_file = solv_dir.join( COOKIE_FILE );
+
+        if solv_file.is_file() && fs::read_to_string( &cookie_file ).map_or( false, |c| c.trim() == cookie ) {
+            info!("Cache of repository {} is up to date", info.repo_alias );
+            return Ok(false);
+        }
+
+        info!("Building cache of repository {}", info.repo_alias );
+        fs::create_dir_all( &solv_dir )?;
+        let tmp_file = NamedTempFile::new_in( &solv_dir )?.into_temp_path();
+
+        let pool = Pool::new();
+        let mut repo = pool.create_repository( &info.repo_alias ).map_err( |e| ZyppError::Internal { message: e.to_string() } )?;
+        let raw_cache = self.raw_cache_path( info );
+        match self.cached_repo_type( info ) {
+            RepoType::RpmMd => Self::load_rpmmd( &mut repo, &raw_cache )?,
+            RepoType::Yast2 => Self::load_susetags( &mut repo, &raw_cache )?,
+            // local rpms are read in place, remote ones were downloaded into the raw cache
+            RepoType::RpmPlainDir => Self::load_plaindir( &mut repo, &Self::local_repo_dir( info ).unwrap_or( raw_cache ) )?,
+            RepoType::None => return Err( RepoManagerError::NotCached( info.repo_alias.clone() ).into() )
+        }
+        // like repo2solv -X in libzypp, patterns are generated from the pattern-* packages
+        repo.add_autopattern()?;
+        repo.write_solv( &tmp_file )?;
+
+        tmp_file.persist( &solv_file ).map_err( |e| e.error )?;
+        fs::write( &cookie_file, cookie )?;
+        Ok(true)
+    }
+
+    /// Reads the raw rpm-md metadata in dir into the repository
+    fn load_rpmmd( repo: &mut Repository, dir: &Path ) -> Result<(), ZyppError> {
+        let repomd_file = dir.join("repodata/repomd.xml");
+        let repomd = RepoMd::read_from_file( &repomd_file )?;
+        let primary = repomd.find("primary").ok_or( RepoMdError::Invalid("no primary data found".to_owned()) )?;
+
+        repo.add_repomdxml( &repomd_file )?;
+        repo.add_rpmmd( dir.join( &primary.location ), None )?;
+        for data in repomd.data.iter() {
+            let language = data.data_type.strip_prefix("susedata.");
+            match data.data_type.as_str() {
+                "susedata" | "filelists" | "other" => repo.extend_rpmmd( dir.join( &data.location ), None )?,
+                _ if language.is_some() => repo.extend_rpmmd( dir.join( &data.location ), language )?,
+                "updateinfo" => repo.add_updateinfoxml( dir.join( &data.location ) )?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the raw susetags metadata in dir into the repository
+    fn load_susetags( repo: &mut Repository, dir: &Path ) -> Result<(), ZyppError> {
+        let content_file = dir.join("content");
+        let content = ContentFile::read_from_file( &content_file )?;
+        let descr_dir = dir.join( &content.descr_dir );
+        let vendor = content.vendor.as_deref();
+
+        repo.add_content( &content_file )?;
+        let packages = content.find_meta("packages").ok_or( SusetagsError::Invalid("no packages file found".to_owned()) )?;
+        repo.add_susetags( descr_dir.join( &packages.file ), vendor, None )?;
+        if let Some(translations) = content.find_meta("packages.en") {
+            repo.extend_susetags( descr_dir.join( &translations.file ), vendor, Some("en") )?;
+        }
+        if let Some(patterns) = content.find_meta("patterns") {
+            for pattern in read_patterns_list( descr_dir.join( &patterns.file ) )? {
+                // the file was stored under the name from the content file
+                let Some(entry) = content.find_meta( &pattern ) else {
+                    continue;
+                };
+                let pattern_file = descr_dir.join( &entry.file );
+                if pattern_file.is_file() {
+                    repo.add_susetags( pattern_file, vendor, None )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the headers of all rpms in dir into the repository, broken rpms are skipped
+    fn load_plaindir( repo: &mut Repository, dir: &Path ) -> Result<(), ZyppError> {
+        for entry in plaindir::scan_dir( dir )? {
+            if let Err(e) = repo.add_rpm( dir.join( &entry.location ), &entry.location ) {
+                warn!("Skipping {} in repository {}. {}", entry.location, repo.name(), e );
+            }
+        }
+        repo.internalize();
+        Ok(())
+    }
+
+    /// Loads the cached .solv file of a repository into the pool, the cache is built first if needed
+    pub fn load_from_cache<'p>( &self, pool: &'p mut Pool, info: &RepoInfo ) -> Result<Repository<'p>, ZyppError> {
+        self.build_cache( info )?;
+
+        let mut repo = pool.create_repository( &info.repo_alias ).map_err( |e| ZyppError::Internal { message: e.to_string() } )?;
+        let loaded = repo.add_solv( self.solv_cache_path( info ).join( SOLV_FILE ) );
+        repo.set_zypp_priority( info.priority );
+        let repo_id = repo.id();
+
+        if let Err(e) = loaded {
+            pool.delete_repository( repo_id );
+            return Err( e.into() );
+        }
+        Ok( pool.repository( repo_id ).expect("The repository was just created") )
+    }
+
+    /// Identifies the state of the rpm database, like libsolv's tools do it the cookie is
+    /// calculated from the stat data of the database file. None if there is no rpm database.
+    fn rpmdb_cookie( &self ) -> Result<Option<String>, ZyppError> {
+        let Some(db_file) = RPMDB_FILES.iter().map( |f| self.options.root_dir.join(f) ).find( |f| f.is_file() ) else {
+            return Ok(None);
+        };
+        let meta = fs::metadata( &db_file )?;
+        let mut hasher = Hasher::new( CheckSumType::Sha256 );
+        hasher.update( format!("{} {} {} {}", meta.dev(), meta.ino(), meta.size(), meta.mtime() ).as_bytes() );
+        Ok( Some( hasher.finalize().to_string() ) )
+    }
+
+    /// Loads the installed packages from the rpm database in the root directory into the
+    /// @System repository and marks it as installed in the pool. Like the other repositories
+    /// the result is cached as .solv file, which is only rebuilt if the rpm database changed.
+    pub fn load_system_repository<'p>( &self, pool: &'p mut Pool ) -> Result<Repository<'p>, ZyppError> {
+        let root_dir = self.options.root_dir.to_str().ok_or( ZyppError::Internal { message: "Root directory is not valid UTF-8".to_owned() } )?;
+        pool.set_rootdir( root_dir ).map_err( |e| ZyppError::Internal { message: e.to_string() } )?;
+
+        let solv_dir = self.options.repo_solv_cache_path.join( SYSTEM_REPO_ALIAS );
+        let solv_file = solv_dir.join( SOLV_FILE );
+        let cookie_file = solv_dir.join( COOKIE_FILE );
+        let cookie = self.rpmdb_cookie()?;
+
+        let mut repo = pool.create_repository( SYSTEM_REPO_ALIAS ).map_err( |e| ZyppError::Internal { message: e.to_string() } )?;
+        let loaded = match &cookie {
+            // no rpm database, nothing is installed
+            None => Ok(()),
+            Some(cookie) if solv_file.is_file() && fs::read_to_string( &cookie_file ).map_or( false, |c| c.trim() == cookie ) => {
+                info!("Cache of repository {} is up to date", SYSTEM_REPO_ALIAS );
+                repo.add_solv( &solv_file ).map_err( ZyppError::from )
+            },
+            Some(cookie) => {
+                info!("Building cache of repository {}", SYSTEM_REPO_ALIAS );
+                Self::build_system_cache( &mut repo, &solv_file, &cookie_file, cookie )
+            }
+        };
+        let repo_id = repo.id();
+
+        if let Err(e) = loaded {
+            pool.delete_repository( repo_id );
+            return Err( e );
+        }
+        let repo = pool.repository( repo_id ).expect("The repository was just created");
+        pool.set_installed( &repo );
+        Ok( repo )
+    }
+
+    /// Reads the rpm database into the repository and stores it in the cache, the
+    /// previous cache is used as reference so unchanged headers are not read again
+    fn build_system_cache( repo: &mut Repository, solv_file: &Path, cookie_file: &Path, cookie: &str ) -> Result<(), ZyppError> {
+        let reference = Some( solv_file ).filter( |f| f.is_file() );
+        repo.add_rpmdb( reference )?;
+
+        let solv_dir = solv_file.parent().unwrap_or( Path::new(".") );
+        fs::create_dir_all( solv_dir )?;
+        let tmp_file = NamedTempFile::new_in( solv_dir )?.into_temp_path();
+        repo.write_solv( &tmp_file )?;
+        tmp_file.persist( solv_file ).map_err( |e| e.error )?;
+        fs::write( cookie_file, cookie )?;
+        Ok(())
+    }
+
+    /// The distribution target repositories of a service have to match, either from the options
+    /// or like in libzypp from the register target of the installed base product.
+    fn target_distro( &self ) -> Option<String> {
+        if !self.options.services_target_distro.is_empty() {
+            return Some( self.options.services_target_distro.clone() );
+        }
+
+        let content = fs::read_to_string( self.options.root_dir.join("etc/products.d/baseproduct") ).ok()?;
+        let doc = roxmltree::Document::parse( &content ).ok()?;
+        let target = doc.descendants()
+            .find( |n| n.has_tag_name("target") && n.parent().map_or( false, |p| p.has_tag_name("register") ) )?
+            .text()?
+            .trim();
+        if target.is_empty() { None } else { Some( target.to_owned() ) }
+    }
+
+    /// Turns a repository from the index of a service into a RepoInfo, the alias is prefixed
+    /// with the service alias to keep repositories of different services apart.
+    fn repo_from_index_entry( service_alias: &str, service_url: &Url, entry: RepoIndexEntry ) -> Result<RepoInfo, ZyppError> {
+        let mut repo = RepoInfo::default();
+        repo.repo_alias = format!("{}:{}", service_alias, entry.alias);
+        repo.repo_name = entry.name.unwrap_or_else( || entry.alias.clone() );
+        repo.service = service_alias.to_owned();
+
+        match entry.url {
+            Some(url) => {
+                repo.raw_base_urls.push( url );
+                if let Some(path) = entry.path {
+                    repo.path = PathBuf::from( path );
+                }
+            },
+            None => {
+                // without a url the repository lives below the service url
+                let mut url = service_url.clone();
+                if let Some(path) = entry.path {
+                    let mut segments = url.path_segments_mut().map_err( |_| RepoManagerError::NoServiceUrl( service_alias.to_owned() ) )?;
+                    segments.pop_if_empty();
+                    segments.extend( path.split('/').filter( |s| !s.is_empty() ) );
+                }
+                repo.raw_base_urls.push( url.to_string() );
+            }
+        }
+
+        if let Some(priority) = entry.priority {
+            repo.priority = priority;
+        }
+        if let Some(enabled) = entry.enabled {
+            repo.enabled = enabled;
+        }
+        if let Some(autorefresh) = entry.autorefresh {
+            repo.autorefresh = autorefresh;
+        }
+        if let Some(keep_packages) = entry.keep_packages {
+            repo.keep_packages = keep_packages;
+        }
+        Ok(repo)
+    }
+
+    /// Refreshes all enabled services, returns the result for each of them together with the alias
+    pub async fn refresh_services( &mut self, media: &Manager ) -> Vec<(String, Result<(), ZyppError>)> {
+        let aliases: Vec<String> = self.services.iter().filter( |s| s.enabled ).map( |s| s.service_alias.clone() ).collect();
+        let mut res = Vec::with_capacity( aliases.len() );
+        for alias in aliases {
+            let status = self.refresh_service( media, &alias ).await;
+            if let Err(e) = &status {
+                warn!("Failed to refresh service {}. {}", alias, e );
+            }
+            res.push( (alias, status) );
+        }
+        res
+    }
+
+    /// Downloads the repoindex.xml of a RIS service and adds, updates or removes the
+    /// repositories of the service so they match the index.
+    pub async fn refresh_service( &mut self, media: &Manager, alias: &str ) -> Result<(), ZyppError> {
+        let service = self.services.iter().find( |s| s.service_alias == alias ).ok_or( RepoManagerError::UnknownService( alias.to_owned() ) )?;
+        if service.service_type != ServiceType::Ris {
+            return Err( RepoManagerError::UnsupportedServiceType( service.service_type.to_string() ).into() );
+        }
+        let service_url = service.url.clone().ok_or( RepoManagerError::NoServiceUrl( alias.to_owned() ) )?;
+        let repos_to_enable = service.repos_to_enable.clone();
+        let repos_to_disable = service.repos_to_disable.clone();
+
+        let medium = media.attach( &vec![ service_url.clone() ], &MediaSpec { label: service.service_name.clone(), medianr: 1, verify_data_path: None } ).await?;
+        let index_file = media.fetch( &medium, "repo/repoindex.xml", &FileSpec::default() ).await?;
+        let index = RepoIndex::read_from_file( &index_file )?;
+
+        let target_distro = self.target_distro();
+        let mut wanted = Vec::with_capacity( index.repos.len() );
+        for entry in index.repos {
+            if let (Some(target), Some(repo_target)) = ( &target_distro, &entry.distro_target ) {
+                if target != repo_target {
+                    info!("Skipping repository {} of service {}, its target {} does not match {}", entry.alias, alias, repo_target, target );
+                    continue;
+                }
+            }
+            let index_alias = entry.alias.clone();
+            wanted.push( ( index_alias, Self::repo_from_index_entry( alias, &service_url, entry )? ) );
+        }
+
+        // remove what the service does not provide anymore
+        let obsolete: Vec<String> = self.repositories.iter()
+            .filter( |r| r.service == alias && !wanted.iter().any( |(_, w)| w.repo_alias == r.repo_alias ) )
+            .map( |r| r.repo_alias.clone() )
+            .collect();
+        for repo_alias in obsolete {
+            info!("Removing repository {} which is no longer provided by service {}", repo_alias, alias );
+            self.remove_repository( &repo_alias )?;
+        }
+
+        for (index_alias, mut repo) in wanted {
+            let requested = |list: &Vec<String>| list.iter().any( |a| *a == index_alias || *a == repo.repo_alias );
+            let enable = requested( &repos_to_enable );
+            let disable = requested( &repos_to_disable );
+
+            match self.get_repository( &repo.repo_alias ) {
+                Some(old) if old.service != alias => {
+                    warn!("Repository {} of service {} clashes with an existing repository, skipping it", repo.repo_alias, alias );
+                    continue;
+                },
+                Some(old) => {
+                    // the user might have enabled or disabled the repo, we keep that
+                    repo.enabled = old.enabled;
+                    repo.repo_type = old.repo_type;
+                    if enable { repo.enabled = true; }
+                    if disable { repo.enabled = false; }
+                    let repo_alias = repo.repo_alias.clone();
+                    self.modify_repository( &repo_alias, repo )?;
+                },
+                None => {
+                    if enable { repo.enabled = true; }
+                    if disable { repo.enabled = false; }
+                    info!("Adding repository {} of service {}", repo.repo_alias, alias );
+                    self.add_repository( repo )?;
+                }
+            }
+        }
+
+        // enable and disable requests are only applied once
+        if !repos_to_enable.is_empty() || !repos_to_disable.is_empty() {
+            if let Some(service) = self.services.iter_mut().find( |s| s.service_alias == alias ) {
+                service.repos_to_enable.clear();
+                service.repos_to_disable.clear();
+                if !service.file_path().as_os_str().is_empty() {
+                    service.write_to_file( service.file_path() )?;
+                }
+            }
+        }
+
+        info!("Refreshed service {}", alias );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solv::arch::{base_arch, host_arch};
+    use tribool::Tribool;
+
+    fn basearch_url( repo: &str ) -> Url {
+        Url::parse( &format!("http://example.com/{}/{}", base_arch( &host_arch() ), repo) ).unwrap()
+    }
+
+    fn repo_file_content( manager: &RepoManager, alias: &str ) -> String {
+        fs::read_to_string( manager.get_repository( alias ).unwrap().file_path() ).unwrap()
+    }
+
+    #[test]
+    fn local_repo_dir_is_decoded() {
+        let mut info = RepoInfo::default();
+        info.base_urls.push( Url::parse( "dir:/srv/my%20repos/oss" ).unwrap() );
+        info.path = PathBuf::from( "/sub dir" );
+        assert_eq!( RepoManager::local_repo_dir( &info ), Some( PathBuf::from( "/srv/my repos/oss/sub dir" ) ) );
+
+        info.base_urls = vec![ Url::from_directory_path( "/srv/100% local" ).unwrap() ];
+        info.path = PathBuf::from( "/" );
+        assert_eq!( RepoManager::local_repo_dir( &info ), Some( PathBuf::from( "/srv/100% local" ) ) );
+
+        info.base_urls = vec![ Url::parse( "http://example.com/my%20repos" ).unwrap() ];
+        assert_eq!( RepoManager::local_repo_dir( &info ), None );
+    }
+
+    #[test]
+    fn added_repo_keeps_variables_in_urls() {
+        let root = TempDir::new().unwrap();
+        let mut manager = RepoManager::new( RepoManagerOptions::new( root.path() ) );
+        let mut info = RepoInfo::default();
+        info.repo_alias = "oss".to_owned();
+        info.raw_base_urls.push( "http://example.com/$basearch/oss".to_owned() );
+        manager.add_repository( info ).unwrap();
+        assert!( repo_file_content( &manager, "oss" ).contains("baseurl=http://example.com/$basearch/oss\n") );
+
+        let manager = RepoManager::new( RepoManagerOptions::new( root.path() ) );
+        assert_eq!( manager.get_repository("oss").unwrap().base_urls, vec![ basearch_url("oss") ] );
+    }
+
+    #[test]
+    fn modified_repo_keeps_variables_in_urls() {
+        let root = TempDir::new().unwrap();
+        let mut manager = RepoManager::new( RepoManagerOptions::new( root.path() ) );
+        let mut info = RepoInfo::default();
+        info.repo_alias = "oss".to_owned();
+        info.base_urls.push( Url::parse("http://example.com/oss").unwrap() );
+        manager.add_repository( info ).unwrap();
+
+        let mut new_info = RepoInfo::default();
+        new_info.repo_alias = "oss".to_owned();
+        new_info.raw_base_urls.push( "http://example.com/$basearch/oss".to_owned() );
+        manager.modify_repository( "oss", new_info ).unwrap();
+        assert!( repo_file_content( &manager, "oss" ).contains("baseurl=http://example.com/$basearch/oss\n") );
+
+        let manager = RepoManager::new( RepoManagerOptions::new( root.path() ) );
+        assert_eq!( manager.get_repository("oss").unwrap().base_urls, vec![ basearch_url("oss") ] );
+    }
+
+    #[test]
+    fn repo_of_a_service_index_keeps_its_url() {
+        let root = TempDir::new().unwrap();
+        let mut manager = RepoManager::new( RepoManagerOptions::new( root.path() ) );
+        let service_url = Url::parse("http://example.com/service").unwrap();
+        let entry = RepoIndexEntry { alias: "oss".to_owned(), url: Some( "http://example.com/$basearch/oss".to_owned() ), ..Default::default() };
+        let info = RepoManager::repo_from_index_entry( "svc", &service_url, entry ).unwrap();
+        manager.add_repository( info ).unwrap();
+        assert!( repo_file_content( &manager, "svc:oss" ).contains("baseurl=http://example.com/$basearch/oss\n") );
+
+        let manager = RepoManager::new( RepoManagerOptions::new( root.path() ) );
+        assert_eq!( manager.get_repository("svc:oss").unwrap().base_urls, vec![ basearch_url("oss") ] );
+    }
+
+    #[tokio::test]
+    async fn storing_the_probed_type_keeps_gpgcheck() {
+        let root = TempDir::new().unwrap();
+        let repo_dir = root.path().join("srv/repo");
+        fs::create_dir_all( repo_dir.join("repodata") ).unwrap();
+        fs::write( repo_dir.join("repodata/repomd.xml"), "" ).unwrap();
+        let options = RepoManagerOptions::new( root.path() );
+        fs::create_dir_all( &options.known_repos_path ).unwrap();
+        let repo_file = options.known_repos_path.join("repo.repo");
+        fs::write( &repo_file, format!("[repo]\nbaseurl=dir:{}\ngpgcheck=0\n", repo_dir.display()) ).unwrap();
 
+        let mut manager = RepoManager::new( options );
+        assert_eq!( manager.get_repository("repo").unwrap().raw_gpg_check, Tribool::False );
+        // the refresh itself fails on the empty repomd.xml, after the type was stored
+        manager.refreshMetadata( &Manager::new(), &["repo"] ).await;
+        assert_eq!( manager.get_repository("repo").unwrap().repo_type, RepoType::RpmMd );
+        let content = fs::read_to_string( &repo_file ).unwrap();
+        assert!( content.contains("type=rpm-md\n") );
+        assert!( content.contains("gpgcheck=0\n") );
     }
 }
@@ -0,0 +1,169 @@
+use std::fs;
+use std::path::Path;
+use roxmltree::{Document, Node};
+use thiserror::Error;
+
+use crate::checksum::CheckSum;
+
+#[derive(Error, Debug)]
+pub enum RepoMdError {
+    #[error("Failed to read repomd.xml - {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to parse repomd.xml - {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("Invalid repomd.xml - {0}")]
+    Invalid(String),
+}
+
+/// One <data> entry of a repomd.xml file
+#[derive(Debug, Clone)]
+pub struct RepoMdData {
+    /// e.g. primary, filelists, other, updateinfo
+    pub data_type: String,
+    /// path of the file, relative to the repository root
+    pub location: String,
+    pub checksum: Option<CheckSum>,
+    pub open_checksum: Option<CheckSum>,
+    pub size: Option<u64>,
+    pub open_size: Option<u64>,
+    pub timestamp: Option<u64>,
+}
+
+/// The index file of a rpm-md repository
+#[derive(Debug, Clone, Default)]
+pub struct RepoMd {
+    pub revision: Option<String>,
+    pub data: Vec<RepoMdData>,
+}
+
+fn child<'a, 'input>( node: &Node<'a, 'input>, name: &str ) -> Option<Node<'a, 'input>> {
+    node.children().find( |c| c.is_element() && c.tag_name().name() == name )
+}
+
+fn child_text<'a>( node: &Node<'a, '_>, name: &str ) -> Option<&'a str> {
+    child( node, name ).and_then( |c| c.text() ).map( str::trim )
+}
+
+fn child_u64( node: &Node, name: &str ) -> Result<Option<u64>, RepoMdError> {
+    child_text( node, name )
+        .map( |t| t.parse::<u64>().map_err( |_| RepoMdError::Invalid( format!("{} is not a valid value for {}", t, name) ) ) )
+        .transpose()
+}
+
+fn child_checksum( node: &Node, name: &str ) -> Result<Option<CheckSum>, RepoMdError> {
+    let Some(c) = child( node, name ) else {
+        return Ok(None);
+    };
+    let kind = c.attribute("type").unwrap_or("sha1");
+    let value = c.text().unwrap_or_default();
+    CheckSum::from_type_str( kind, value )
+        .map( Some )
+        .map_err( |e| RepoMdError::Invalid( e.to_string() ) )
+}
+
+impl RepoMd {
+
+    pub fn parse( content: &str ) -> Result<Self, RepoMdError> {
+        let doc = Document::parse( content )?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "repomd" {
+            return Err( RepoMdError::Invalid( format!("Unexpected root element {}", root.tag_name().name() ) ) );
+        }
+
+        let mut repomd = RepoMd {
+            revision: child_text( &root, "revision" ).map( str::to_owned ),
+            ..Default::default()
+        };
+
+        for data in root.children().filter( |c| c.is_element() && c.tag_name().name() == "data" ) {
+            let data_type = data.attribute("type").ok_or( RepoMdError::Invalid("data element without type".to_owned()) )?;
+            let location = child( &data, "location" )
+                .and_then( |l| l.attribute("href") )
+                .ok_or( RepoMdError::Invalid( format!("data element {} has no location", data_type) ) )?;
+
+            repomd.data.push( RepoMdData {
+                data_type: data_type.to_owned(),
+                location: location.to_owned(),
+                checksum: child_checksum( &data, "checksum" )?,
+                open_checksum: child_checksum( &data, "open-checksum" )?,
+                size: child_u64( &data, "size" )?,
+                open_size: child_u64( &data, "open-size" )?,
+                timestamp: child_u64( &data, "timestamp" )?,
+            });
+        }
+        Ok( repomd )
+    }
+
+    pub fn read_from_file<P: AsRef<Path>>( file_path: P ) -> Result<Self, RepoMdError> {
+        Self::parse( &fs::read_to_string( file_path )? )
+    }
+
+    pub fn find( &self, data_type: &str ) -> Option<&RepoMdData> {
+        self.data.iter().find( |d| d.data_type == data_type )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::CheckSumType;
+
+    const SHA1: &str = "a9993e364706816aba3e25717850c26c9cd0d89d";
+    const SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn repomd( data: &str ) -> String {
+        format!( r#"<?xml version="1.0" encoding="UTF-8"?>
+<repomd xmlns="http://linux.duke.edu/metadata/repo" xmlns:rpm="http://linux.duke.edu/metadata/rpm">
+  <revision>1701432000</revision>
+  {}
+</repomd>"#, data )
+    }
+
+    #[test]
+    fn parses_data_entries() {
+        let content = repomd( &format!( r#"
+  <data type="primary">
+    <checksum type="sha256">{}</checksum>
+    <open-checksum type="sha">{}</open-checksum>
+    <location href="repodata/primary.xml.gz"/>
+    <timestamp>1701432000</timestamp>
+    <size>1234</size>
+    <open-size>5678</open-size>
+  </data>
+  <data type="filelists">
+    <location href="repodata/filelists.xml.gz"/>
+  </data>"#, SHA256, SHA1 ) );
+        let repomd = RepoMd::parse( &content ).unwrap();
+        assert_eq!( repomd.revision.as_deref(), Some( "1701432000" ) );
+        assert_eq!( repomd.data.len(), 2 );
+
+        let primary = repomd.find( "primary" ).unwrap();
+        assert_eq!( primary.location, "repodata/primary.xml.gz" );
+        assert_eq!( primary.checksum, Some( CheckSum::new( CheckSumType::Sha256, SHA256 ).unwrap() ) );
+        // rpm-md calls sha1 "sha"
+        assert_eq!( primary.open_checksum.as_ref().map( CheckSum::kind ), Some( CheckSumType::Sha1 ) );
+        assert_eq!( ( primary.size, primary.open_size, primary.timestamp ), ( Some( 1234 ), Some( 5678 ), Some( 1701432000 ) ) );
+
+        let filelists = repomd.find( "filelists" ).unwrap();
+        assert!( filelists.checksum.is_none() && filelists.size.is_none() );
+        assert!( repomd.find( "other" ).is_none() );
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        let missing_location = repomd( r#"<data type="primary"><size>1</size></data>"# );
+        assert!( matches!( RepoMd::parse( &missing_location ), Err( RepoMdError::Invalid( ref msg ) ) if msg.contains( "primary" ) ) );
+
+        let bad_size = repomd( r#"<data type="primary"><location href="p.xml"/><size>-1</size></data>"# );
+        assert!( matches!( RepoMd::parse( &bad_size ), Err( RepoMdError::Invalid( _ ) ) ) );
+
+        let short_checksum = repomd( r#"<data type="primary"><location href="p.xml"/><checksum type="sha256">abc</checksum></data>"# );
+        assert!( matches!( RepoMd::parse( &short_checksum ), Err( RepoMdError::Invalid( _ ) ) ) );
+
+        let unknown_type = repomd( &format!( r#"<data type="primary"><location href="p.xml"/><checksum type="crc">{}</checksum></data>"#, SHA1 ) );
+        assert!( matches!( RepoMd::parse( &unknown_type ), Err( RepoMdError::Invalid( _ ) ) ) );
+
+        assert!( matches!( RepoMd::parse( "<metalink/>" ), Err( RepoMdError::Invalid( _ ) ) ) );
+        assert!( matches!( RepoMd::parse( "<repomd>" ), Err( RepoMdError::Xml( _ ) ) ) );
+    }
+}
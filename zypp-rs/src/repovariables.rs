@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use log::{debug, warn};
+
+use crate::solv::arch::{base_arch, host_arch};
+
+/// Variables that can be used in the URLs of a repo file, e.g. $releasever or ${basearch}.
+///
+/// Expansion follows the rules of libzypp's RepoVarExpand:
+///  - `$name` and `${name}` are replaced by the value of the variable
+///  - `${name:-word}` expands to word if name is unset or empty, to the value of name otherwise
+///  - `${name:+word}` expands to word if name is set and not empty, to nothing otherwise
+///  - a backslash escapes a following `$`, `\` or `}`
+///  - references to unknown variables are left untouched
+#[derive(Debug, Default, Clone)]
+pub struct RepoVariables {
+  vars: HashMap<String, String>
+}
+
+fn is_var_char( c: char ) -> bool {
+  c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_valid_var_name( name: &str ) -> bool {
+  !name.is_empty() && name.chars().all(is_var_char)
+}
+
+/// Finds the closing brace of a ${...} expression, nested expressions and escaped braces are skipped.
+fn find_closing_brace( s: &str ) -> Option<usize> {
+  let mut depth = 0;
+  let mut escaped = false;
+  for (i, c) in s.char_indices() {
+    if escaped {
+      escaped = false;
+      continue;
+    }
+    match c {
+      '\\' => escaped = true,
+      '{' => depth += 1,
+      '}' if depth == 0 => return Some(i),
+      '}' => depth -= 1,
+      _ => {}
+    }
+  }
+  None
+}
+
+impl RepoVariables {
+
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  /// Initializes the builtin variables for the system in root_dir and adds the custom
+  /// variables from vars_path on top. Like in libzypp the environment variable
+  /// ZYPP_REPO_RELEASEVER overrides the release version of the target.
+  pub fn for_target<P: AsRef<Path>, V: AsRef<Path>>( root_dir: P, vars_path: V ) -> Self {
+    let mut vars = Self::new();
+
+    let arch = host_arch();
+    vars.set( "arch", &arch );
+    vars.set( "basearch", base_arch( &arch ) );
+
+    let releasever = env::var("ZYPP_REPO_RELEASEVER").ok()
+      .or_else(|| Self::read_os_release_version( root_dir.as_ref().join("etc/os-release") ) );
+    if let Some(releasever) = releasever {
+      vars.set_releasever( &releasever );
+    }
+
+    vars.load_dir( vars_path );
+    vars
+  }
+
+  fn read_os_release_version<P: AsRef<Path>>( os_release: P ) -> Option<String> {
+    let content = fs::read_to_string( os_release ).ok()?;
+    content.lines()
+      .filter_map( |l| l.trim().strip_prefix("VERSION_ID=") )
+      .map( |v| v.trim_matches(|c| c == '"' || c == '\'' ).to_owned() )
+      .next()
+  }
+
+  /// Sets $releasever together with the derived $releasever_major and $releasever_minor
+  pub fn set_releasever( &mut self, releasever: &str ) {
+    let (major, minor) = releasever.split_once('.').unwrap_or( (releasever, "") );
+    self.set( "releasever", releasever );
+    self.set( "releasever_major", major );
+    self.set( "releasever_minor", minor );
+  }
+
+  /// Reads custom variables from a vars.d directory, the file name is the name of the
+  /// variable and the first line of the file its value. Invalid names are ignored.
+  pub fn load_dir<P: AsRef<Path>>( &mut self, vars_path: P ) {
+    let entries = match fs::read_dir( vars_path.as_ref() ) {
+      Ok(e) => e,
+      Err(e) => {
+        debug!("Not reading repo variables from {}: {}", vars_path.as_ref().to_string_lossy(), e );
+        return;
+      }
+    };
+
+    for entry in entries.filter_map( |e| e.ok() ).filter( |e| e.path().is_file() ) {
+      let name = entry.file_name().to_string_lossy().to_string();
+      if !is_valid_var_name( &name ) {
+        warn!("Ignoring repo variable file with invalid name: {}", name );
+        continue;
+      }
+      match fs::read_to_string( entry.path() ) {
+        Ok(content) => self.set( &name, content.lines().next().unwrap_or_default().trim() ),
+        Err(e) => warn!("Failed to read repo variable {}: {}", name, e )
+      }
+    }
+  }
+
+  pub fn set( &mut self, name: &str, value: &str ) {
+    self.vars.insert( name.to_owned(), value.to_owned() );
+  }
+
+  pub fn get( &self, name: &str ) -> Option<&str> {
+    self.vars.get(name).map(String::as_str)
+  }
+
+  /// Replaces all variables in the input string
+  pub fn expand( &self, input: &str ) -> String {
+    let mut out = String::with_capacity( input.len() );
+    self.expand_into( input, &mut out );
+    out
+  }
+
+  fn expand_into( &self, input: &str, out: &mut String ) {
+    let mut rest = input;
+    while let Some(pos) = rest.find(['$', '\\']) {
+      out.push_str( &rest[..pos] );
+      let tail = &rest[pos..];
+
+      if let Some(escaped) = tail.strip_prefix('\\') {
+        match escaped.chars().next() {
+          Some(c @ ('$' | '\\' | '}')) => out.push(c),
+          Some(c) => {
+            out.push('\\');
+            out.push(c);
+          },
+          None => out.push('\\')
+        }
+        rest = &escaped[escaped.chars().next().map_or(0, char::len_utf8)..];
+        continue;
+      }
+
+      let consumed = self.expand_variable( tail, out );
+      rest = &tail[consumed..];
+    }
+    out.push_str( rest );
+  }
+
+  /// Expands the variable expression at the start of tail, which always starts with '$'.
+  /// Returns the number of bytes that were consumed.
+  fn expand_variable( &self, tail: &str, out: &mut String ) -> usize {
+    let value_of = |name: &str| self.vars.get(name).filter(|v| !v.is_empty() );
+
+    let body = &tail[1..];
+    let Some(braced) = body.strip_prefix('{') else {
+      let name_len = body.find( |c| !is_var_char(c) ).unwrap_or( body.len() );
+      if name_len == 0 {
+        out.push('$');
+        return 1;
+      }
+      match self.vars.get( &body[..name_len] ) {
+        Some(v) => out.push_str(v),
+        None => out.push_str( &tail[..1+name_len] )
+      }
+      return 1 + name_len;
+    };
+
+    let name_len = braced.find( |c| !is_var_char(c) ).unwrap_or( braced.len() );
+    let name = &braced[..name_len];
+    let after = &braced[name_len..];
+    if !name.is_empty() {
+      if after.starts_with('}') {
+        let consumed = 2 + name_len + 1;
+        match self.vars.get(name) {
+          Some(v) => out.push_str(v),
+          None => out.push_str( &tail[..consumed] )
+        }
+        return consumed;
+      }
+
+      if after.starts_with(":-") || after.starts_with(":+") {
+        let word_start = 2 + name_len + 2;
+        if let Some(word_len) = find_closing_brace( &tail[word_start..] ) {
+          let word = &tail[word_start..word_start+word_len];
+          if after.starts_with(":-") {
+            match value_of(name) {
+              Some(v) => out.push_str(v),
+              None => self.expand_into( word, out )
+            }
+          } else if value_of(name).is_some() {
+            self.expand_into( word, out );
+          }
+          return word_start + word_len + 1;
+        }
+      }
+    }
+
+    // not a valid expression, keep it as it is
+    out.push('$');
+    1
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn vars() -> RepoVariables {
+    let mut vars = RepoVariables::new();
+    vars.set( "releasever", "15.5" );
+    vars.set( "basearch", "x86_64" );
+    vars.set( "empty", "" );
+    vars
+  }
+
+  #[test]
+  fn plain_and_braced_references() {
+    let vars = vars();
+    assert_eq!( vars.expand( "http://host/$releasever/$basearch/" ), "http://host/15.5/x86_64/" );
+    assert_eq!( vars.expand( "http://host/${releasever}_${basearch}" ), "http://host/15.5_x86_64" );
+    assert_eq!( vars.expand( "$releasever.1" ), "15.5.1" );
+    assert_eq!( vars.expand( "a$" ), "a$" );
+  }
+
+  #[test]
+  fn default_and_alternate_values() {
+    let vars = vars();
+    assert_eq!( vars.expand( "${releasever:-leap}" ), "15.5" );
+    assert_eq!( vars.expand( "${empty:-leap}" ), "leap" );
+    assert_eq!( vars.expand( "${unset:-$basearch}" ), "x86_64" );
+    assert_eq!( vars.expand( "${releasever:+/update}" ), "/update" );
+    assert_eq!( vars.expand( "${empty:+/update}" ), "" );
+    assert_eq!( vars.expand( "${unset:+/update}" ), "" );
+    // nested expressions and escaped braces in the word
+    assert_eq!( vars.expand( "${unset:-${releasever:+sle}}" ), "sle" );
+    assert_eq!( vars.expand( "${unset:-a\\}b}" ), "a}b" );
+  }
+
+  #[test]
+  fn escaped_dollars_are_literal() {
+    let vars = vars();
+    assert_eq!( vars.expand( "\\$releasever" ), "$releasever" );
+    assert_eq!( vars.expand( "\\\\$releasever" ), "\\15.5" );
+    assert_eq!( vars.expand( "\\x" ), "\\x" );
+    assert_eq!( vars.expand( "a\\" ), "a\\" );
+  }
+
+  #[test]
+  fn unknown_variables_stay_literal() {
+    let vars = vars();
+    assert_eq!( vars.expand( "$unknown/${unknown}/$basearch" ), "$unknown/${unknown}/x86_64" );
+    // the empty variable is known and expands to nothing
+    assert_eq!( vars.expand( "a${empty}b" ), "ab" );
+    // invalid expressions are kept as they are
+    assert_eq!( vars.expand( "${}" ), "${}" );
+    assert_eq!( vars.expand( "${releasever" ), "${releasever" );
+    assert_eq!( vars.expand( "${releasever:-open" ), "${releasever:-open" );
+  }
+
+  #[test]
+  fn target_defaults_and_vars_d_overrides() {
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir_all( root.path().join("etc/zypp/vars.d") ).unwrap();
+    fs::write( root.path().join("etc/os-release"), "NAME=\"openSUSE Leap\"\nVERSION_ID=\"15.5\"\n" ).unwrap();
+    let vars_d = root.path().join("etc/zypp/vars.d");
+
+    let vars = RepoVariables::for_target( root.path(), &vars_d );
+    if env::var("ZYPP_REPO_RELEASEVER").is_err() {
+      assert_eq!( vars.get("releasever"), Some("15.5") );
+      assert_eq!( vars.get("releasever_major"), Some("15") );
+      assert_eq!( vars.get("releasever_minor"), Some("5") );
+    }
+    let arch = host_arch();
+    assert_eq!( vars.get("arch"), Some( arch.as_str() ) );
+    assert_eq!( vars.get("basearch"), Some( base_arch( &arch ) ) );
+
+    fs::write( vars_d.join("basearch"), "riscv64\nignored\n" ).unwrap();
+    fs::write( vars_d.join("sle_product"), " SLES \n" ).unwrap();
+    fs::write( vars_d.join("invalid-name"), "x" ).unwrap();
+    let vars = RepoVariables::for_target( root.path(), &vars_d );
+    assert_eq!( vars.get("basearch"), Some("riscv64") );
+    assert_eq!( vars.get("sle_product"), Some("SLES") );
+    assert_eq!( vars.get("invalid-name"), None );
+    assert_eq!( vars.expand( "/$sle_product/$basearch" ), "/SLES/riscv64" );
+  }
+}
@@ -0,0 +1,283 @@
+use configparser::ini::Ini;
+use url::Url;
+use std::str::FromStr;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::io;
+use thiserror::Error;
+use log::warn;
+use roxmltree::Document;
+
+use crate::inifile::IniFile;
+use crate::repoinfo::{ParseRepoFileError, parse_bool, bool_value, parse_url};
+use crate::repovariables::RepoVariables;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("The service type {0} is not known")]
+    UnknownServiceType(String),
+
+    #[error(transparent)]
+    ParseRepoFileError(#[from] ParseRepoFileError),
+
+    #[error("Invalid repoindex.xml - {0}")]
+    InvalidRepoIndex(String),
+
+    #[error("IO Error - {0}")]
+    Io(#[from] io::Error)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceType {
+  None,
+  /// Repository Index Service, the service url provides a repo/repoindex.xml
+  Ris,
+  /// the repositories are provided by a plugin script
+  Plugin,
+}
+
+impl Default for ServiceType {
+  fn default() -> Self {
+    Self::None
+  }
+}
+
+impl FromStr for ServiceType {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "ris"|"nu" => Ok(ServiceType::Ris),
+      "plugin" => Ok(ServiceType::Plugin),
+      "none" => Ok(ServiceType::None),
+      &_ => Err(Error::UnknownServiceType(String::from(s))),
+    }
+  }
+}
+
+impl ToString for ServiceType {
+  fn to_string(&self) -> String {
+    match self {
+      ServiceType::None => "NONE".to_owned(),
+      ServiceType::Ris => "ris".to_owned(),
+      ServiceType::Plugin => "plugin".to_owned(),
+    }
+  }
+}
+
+/// A service as defined in a .service file, services manage a set of repositories for us
+#[derive(Debug)]
+pub struct ServiceInfo
+{
+  pub service_alias: String,
+  pub service_name: String,
+  pub service_type: ServiceType,
+  pub enabled: bool,
+  pub autorefresh: bool,
+  /// url as written in the service file, before any variables were replaced
+  pub raw_url: String,
+  /// url with all repo variables replaced, see expand_variables
+  pub url: Option<Url>,
+  /// aliases of repositories that should be enabled on the next refresh
+  pub repos_to_enable: Vec<String>,
+  /// aliases of repositories that should be disabled on the next refresh
+  pub repos_to_disable: Vec<String>,
+  file_path: PathBuf
+}
+
+impl Default for ServiceInfo {
+  fn default() -> Self {
+    Self {
+      service_alias: Default::default(),
+      service_name: Default::default(),
+      service_type: Default::default(),
+      enabled: true,
+      autorefresh: false,
+      raw_url: Default::default(),
+      url: None,
+      repos_to_enable: Default::default(),
+      repos_to_disable: Default::default(),
+      file_path: Default::default()
+    }
+  }
+}
+
+impl ServiceInfo {
+
+  fn from_section( sec: &str, prop: &HashMap<String, Option<String>> ) -> Result<ServiceInfo, Error> {
+    let mut info = ServiceInfo{ service_alias: String::from(sec), ..Default::default() };
+    for ( key, maybe_val ) in prop.iter() {
+      let Some(val) = maybe_val else {
+        continue;
+      };
+      let first_val = val.split('\n').next().unwrap_or_default().trim();
+
+      match key.to_lowercase().as_str() {
+        "type" => info.service_type = ServiceType::from_str(first_val)?,
+        "name" => info.service_name = first_val.to_owned(),
+        "enabled" => info.enabled = parse_bool( key, first_val )?,
+        "autorefresh" => info.autorefresh = parse_bool( key, first_val )?,
+        "url" => {
+          info.raw_url = first_val.to_owned();
+          // urls using variables can only be checked after expand_variables was called
+          if !first_val.contains('$') {
+            info.url = Some( parse_url( key, first_val )? );
+          }
+        },
+        "repostoenable" => info.repos_to_enable = val.split_whitespace().map(str::to_owned).collect(),
+        "repostodisable" => info.repos_to_disable = val.split_whitespace().map(str::to_owned).collect(),
+        &_ => warn!("Seen unknown key {} with value {}", key, val), //ignore unknown fields but log them
+      }
+    }
+    Ok(info)
+  }
+
+  pub fn read_from_file<P: AsRef<Path>>( file_path: P ) -> Result<Vec<ServiceInfo>, Error> {
+    let mut res: Vec<ServiceInfo> = Vec::new();
+
+    // aliases are case sensitive
+    let mut config = Ini::new_cs();
+    config.set_multiline(true);
+
+    let service_file = config.load(file_path.as_ref()).map_err(ParseRepoFileError::ParserError)?;
+
+    for ( sec, prop ) in service_file.iter() {
+      if sec.is_empty() {
+        continue;
+      }
+      let mut info = ServiceInfo::from_section( sec, prop )?;
+      info.set_file_path( &file_path );
+      res.push( info );
+    }
+    Ok(res)
+  }
+
+  /// Replaces the repo variables in the raw url and stores the result in url
+  pub fn expand_variables( &mut self, vars: &RepoVariables ) -> Result<(), Error> {
+    self.url = Some( parse_url( "url", &vars.expand( &self.raw_url ) )? );
+    Ok(())
+  }
+
+  /// Stores all our settings in the section named after the alias, see RepoInfo::store_in
+  pub(crate) fn store_in( &self, doc: &mut IniFile ) {
+    let alias = self.service_alias.as_str();
+    let mut put = |key: &str, values: Vec<String>| {
+      if values.is_empty() {
+        doc.remove_key( alias, key );
+      } else {
+        doc.set( alias, key, &values );
+      }
+    };
+
+    let raw_url = if self.raw_url.is_empty() {
+      self.url.as_ref().map(Url::to_string).unwrap_or_default()
+    } else {
+      self.raw_url.clone()
+    };
+
+    put( "name", if self.service_name.is_empty() { Vec::new() } else { vec![ self.service_name.clone() ] } );
+    put( "enabled", bool_value(self.enabled) );
+    put( "autorefresh", bool_value(self.autorefresh) );
+    put( "url", if raw_url.is_empty() { Vec::new() } else { vec![ raw_url ] } );
+    put( "type", vec![ self.service_type.to_string() ] );
+    put( "repostoenable", if self.repos_to_enable.is_empty() { Vec::new() } else { vec![ self.repos_to_enable.join(" ") ] } );
+    put( "repostodisable", if self.repos_to_disable.is_empty() { Vec::new() } else { vec![ self.repos_to_disable.join(" ") ] } );
+  }
+
+  /// Writes the service into the given .service file, keeping everything else in the file intact
+  pub fn write_to_file<P: AsRef<Path>>( &self, file_path: P ) -> Result<(), Error> {
+    let mut doc = if file_path.as_ref().exists() {
+      IniFile::load( &file_path )?
+    } else {
+      IniFile::default()
+    };
+    self.store_in( &mut doc );
+    doc.save( file_path )?;
+    Ok(())
+  }
+
+  /// The .service file this service was read from
+  pub fn file_path( &self ) -> &PathBuf {
+    &self.file_path
+  }
+
+  pub fn set_file_path<P: AsRef<Path>>( & mut self, new_path: P ) {
+    self.file_path = new_path.as_ref().to_path_buf();
+  }
+}
+
+/// One repository in the repoindex.xml of a RIS service
+#[derive(Debug, Clone, Default)]
+pub struct RepoIndexEntry {
+  pub alias: String,
+  pub name: Option<String>,
+  pub url: Option<String>,
+  pub path: Option<String>,
+  pub distro_target: Option<String>,
+  pub priority: Option<u32>,
+  pub enabled: Option<bool>,
+  pub autorefresh: Option<bool>,
+  pub keep_packages: Option<bool>,
+}
+
+/// The index file of a Repository Index Service
+#[derive(Debug, Clone, Default)]
+pub struct RepoIndex {
+  /// seconds until the service should be refreshed again
+  pub ttl: Option<u64>,
+  pub repos: Vec<RepoIndexEntry>,
+}
+
+impl RepoIndex {
+
+  pub fn parse( content: &str ) -> Result<Self, Error> {
+    let invalid = |msg: String| Error::InvalidRepoIndex(msg);
+
+    let doc = Document::parse( content ).map_err( |e| invalid( e.to_string() ) )?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "repoindex" {
+      return Err( invalid( format!("Unexpected root element {}", root.tag_name().name() ) ) );
+    }
+
+    let mut index = RepoIndex {
+      ttl: root.attribute("ttl").and_then( |t| t.parse().ok() ),
+      ..Default::default()
+    };
+
+    // <var name="..." value="..."/> elements define replacements for %{name} in the repo attributes
+    let vars: Vec<(String, &str)> = root.children()
+      .filter( |c| c.is_element() && c.tag_name().name() == "var" )
+      .filter_map( |c| Some( ( format!("%{{{}}}", c.attribute("name")?), c.attribute("value").unwrap_or_default() ) ) )
+      .collect();
+    let attr = |node: &roxmltree::Node, name: &str| -> Option<String> {
+      node.attribute(name).map( |v| vars.iter().fold( v.to_owned(), |acc, (var, value)| acc.replace( var.as_str(), value ) ) )
+    };
+    let bool_attr = |node: &roxmltree::Node, name: &str| -> Result<Option<bool>, Error> {
+      attr( node, name ).map( |v| parse_bool( name, &v ) ).transpose().map_err( Error::from )
+    };
+
+    for repo in root.children().filter( |c| c.is_element() && c.tag_name().name() == "repo" ) {
+      let alias = attr( &repo, "alias" ).ok_or( invalid( "repo element without alias".to_owned() ) )?;
+      let priority = attr( &repo, "priority" )
+        .map( |p| p.parse::<u32>().map_err( |_| invalid( format!("invalid priority {} for repo {}", p, alias) ) ) )
+        .transpose()?;
+
+      index.repos.push( RepoIndexEntry {
+        name: attr( &repo, "name" ),
+        url: attr( &repo, "url" ),
+        path: attr( &repo, "path" ),
+        distro_target: attr( &repo, "distro_target" ),
+        priority,
+        enabled: bool_attr( &repo, "enabled" )?,
+        autorefresh: bool_attr( &repo, "autorefresh" )?,
+        keep_packages: bool_attr( &repo, "keeppackages" )?,
+        alias,
+      });
+    }
+    Ok(index)
+  }
+
+  pub fn read_from_file<P: AsRef<Path>>( file_path: P ) -> Result<Self, Error> {
+    Self::parse( &std::fs::read_to_string( file_path )? )
+  }
+}
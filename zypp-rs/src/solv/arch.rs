@@ -0,0 +1,83 @@
+/// The rpm architecture of the machine we are running on, like zypp determines it.
+/// On x86_64 this includes the microarchitecture level, e.g. x86_64_v3, so packages
+/// built for newer CPUs can be used where they are supported.
+pub fn host_arch () -> String {
+    let arch = match std::env::consts::ARCH {
+        "x86" => "i686",
+        "x86_64" => x86_64_level(),
+        "powerpc" => "ppc",
+        "powerpc64" if cfg!( target_endian = "little" ) => "ppc64le",
+        "powerpc64" => "ppc64",
+        "arm" => "armv7hl",
+        "loongarch64" => "loongarch64",
+        other => other,
+    };
+    arch.to_owned()
+}
+
+/// The architecture an arch belongs to, like repo files use it for $basearch,
+/// e.g. x86_64 for x86_64_v3 and i386 for i686
+pub fn base_arch ( arch: &str ) -> &str {
+    match arch {
+        "i486" | "i586" | "i686" | "athlon" => "i386",
+        arch if arch.starts_with( "x86_64_v" ) => "x86_64",
+        arch => arch,
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn x86_64_level () -> &'static str {
+    use std::arch::is_x86_feature_detected as has;
+
+    let v2 = has!("cmpxchg16b") && has!("popcnt") && has!("sse3") && has!("ssse3")
+        && has!("sse4.1") && has!("sse4.2");
+    let v3 = v2 && has!("avx") && has!("avx2") && has!("bmi1") && has!("bmi2")
+        && has!("f16c") && has!("fma") && has!("lzcnt") && has!("xsave");
+    let v4 = v3 && has!("avx512f") && has!("avx512bw") && has!("avx512cd")
+        && has!("avx512dq") && has!("avx512vl");
+    match (v2, v3, v4) {
+        (_, _, true) => "x86_64_v4",
+        (_, true, _) => "x86_64_v3",
+        (true, _, _) => "x86_64_v2",
+        _ => "x86_64"
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn x86_64_level () -> &'static str {
+    "x86_64"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_archs () {
+        let table = [
+            ( "noarch", "noarch" ),
+            ( "i486", "i386" ),
+            ( "i686", "i386" ),
+            ( "athlon", "i386" ),
+            ( "x86_64", "x86_64" ),
+            ( "x86_64_v2", "x86_64" ),
+            ( "x86_64_v3", "x86_64" ),
+            ( "aarch64", "aarch64" ),
+            ( "ppc64le", "ppc64le" ),
+        ];
+        for (arch, base) in table {
+            assert_eq!( base_arch( arch ), base, "{}", arch );
+        }
+    }
+
+    #[test]
+    fn host_arch_is_an_rpm_arch () {
+        let arch = host_arch();
+        assert_ne!( arch, "noarch" );
+        assert!( ![ "x86", "powerpc", "powerpc64", "arm" ].contains( &arch.as_str() ), "{}", arch );
+        if cfg!( target_arch = "x86_64" ) {
+            assert!( [ "x86_64", "x86_64_v2", "x86_64_v3", "x86_64_v4" ].contains( &arch.as_str() ), "{}", arch );
+            assert_eq!( base_arch( &arch ), "x86_64" );
+        }
+    }
+}
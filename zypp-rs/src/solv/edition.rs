@@ -0,0 +1,174 @@
+use solv_sys as raw;
+use std::cmp::Ordering;
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::c_int;
+
+use super::pool::Pool;
+
+thread_local! {
+    /// libsolv needs a pool to compare editions, it only looks at the distribution type,
+    /// which is rpm for every new pool
+    static COMPARE_POOL: Pool = Pool::new();
+}
+
+/// The version of a package as "epoch:version-release", epoch and release are optional.
+/// Editions are compared with libsolv and therefore follow the rpm rules, so "1.10" is
+/// newer than "1.9" and "1.0~rc1" is older than "1.0".
+#[derive(Debug, Clone, Default)]
+pub struct Edition {
+    evr: String
+}
+
+impl Edition {
+
+    pub fn new ( evr: &str ) -> Self {
+        Self { evr: evr.to_owned() }
+    }
+
+    pub fn from_parts ( epoch: u32, version: &str, release: Option<&str> ) -> Self {
+        let mut evr = String::new();
+        if epoch != 0 {
+            evr.push_str( &format!("{}:", epoch) );
+        }
+        evr.push_str( version );
+        if let Some(release) = release {
+            evr.push_str( &format!("-{}", release) );
+        }
+        Self { evr }
+    }
+
+    pub fn as_str ( &self ) -> &str {
+        &self.evr
+    }
+
+    fn split_epoch ( &self ) -> ( Option<&str>, &str ) {
+        match self.evr.split_once( ':' ) {
+            Some((epoch, vr)) if !epoch.is_empty() && epoch.bytes().all( |b| b.is_ascii_digit() ) => ( Some(epoch), vr ),
+            _ => ( None, &self.evr )
+        }
+    }
+
+    /// The epoch, 0 if it is not set
+    pub fn epoch ( &self ) -> u32 {
+        self.split_epoch().0.and_then( |e| e.parse().ok() ).unwrap_or( 0 )
+    }
+
+    pub fn version ( &self ) -> &str {
+        let vr = self.split_epoch().1;
+        vr.rsplit_once( '-' ).map_or( vr, |(v, _)| v )
+    }
+
+    pub fn release ( &self ) -> Option<&str> {
+        self.split_epoch().1.rsplit_once( '-' ).map( |(_, r)| r )
+    }
+
+    fn evrcmp ( &self, other: &Self, mode: u32 ) -> Ordering {
+        // editions never contain a nul byte when they come from libsolv, treat broken ones as empty
+        let a = CString::new( self.evr.as_str() ).unwrap_or_default();
+        let b = CString::new( other.evr.as_str() ).unwrap_or_default();
+        let res = COMPARE_POOL.with( |pool| unsafe {
+            raw::pool_evrcmp_str( pool.pool, a.as_ptr(), b.as_ptr(), mode as c_int )
+        });
+        res.cmp( &0 )
+    }
+
+    /// Like ==, but a missing release on either side matches any release, so "1.0"
+    /// matches "1.0-3". This is how versioned dependencies like "foo = 1.0" are resolved.
+    pub fn matches ( &self, other: &Self ) -> bool {
+        self.evrcmp( other, raw::EVRCMP_MATCH ) == Ordering::Equal
+    }
+}
+
+impl From<&str> for Edition {
+    fn from( evr: &str ) -> Self {
+        Self::new( evr )
+    }
+}
+
+impl fmt::Display for Edition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str( &self.evr )
+    }
+}
+
+impl PartialEq for Edition {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp( other ) == Ordering::Equal
+    }
+}
+
+impl Eq for Edition {}
+
+impl PartialOrd for Edition {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some( self.cmp( other ) )
+    }
+}
+
+impl Ord for Edition {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.evrcmp( other, raw::EVRCMP_COMPARE )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordering () {
+        // (older, newer)
+        let table = [
+            // the epoch beats everything else
+            ( "9.9-9", "1:1.0-1" ),
+            ( "1:2.0-1", "2:1.0-1" ),
+            // versions are compared segment by segment, numbers numerically
+            ( "1.9-1", "1.10-1" ),
+            ( "1.0-1", "1.0.1-1" ),
+            ( "1.0a-1", "1.1-1" ),
+            // the release only breaks ties of the version
+            ( "1.0-2", "1.1-1" ),
+            ( "1.0-1", "1.0-2" ),
+            ( "1.0-1.9", "1.0-1.10" ),
+            // ~ sorts before everything, even the end of the version
+            ( "1.0~rc1-1", "1.0-1" ),
+            ( "1.0~rc1-1", "1.0~rc2-1" ),
+            ( "1.0~~-1", "1.0~rc1-1" ),
+        ];
+        for (older, newer) in table {
+            let (older, newer) = ( Edition::new( older ), Edition::new( newer ) );
+            assert!( older < newer, "{} < {}", older, newer );
+            assert!( newer > older, "{} > {}", newer, older );
+        }
+
+        assert_eq!( Edition::new( "0:1.0-1" ), Edition::new( "1.0-1" ) );
+        assert_eq!( Edition::new( "1.01-1" ), Edition::new( "1.1-1" ) );
+        assert_ne!( Edition::new( "1.0" ), Edition::new( "1.0-1" ) );
+    }
+
+    #[test]
+    fn missing_release_matches_any_release () {
+        assert!( Edition::new( "1.0" ).matches( &Edition::new( "1.0-3" ) ) );
+        assert!( Edition::new( "1.0-3" ).matches( &Edition::new( "1.0" ) ) );
+        assert!( !Edition::new( "1.0-2" ).matches( &Edition::new( "1.0-3" ) ) );
+        assert!( !Edition::new( "1:1.0" ).matches( &Edition::new( "1.0-3" ) ) );
+    }
+
+    #[test]
+    fn parts () {
+        let table = [
+            // (evr, epoch, version, release)
+            ( "1.0", 0, "1.0", None ),
+            ( "2:1.0-3.1", 2, "1.0", Some( "3.1" ) ),
+            ( "1.0-rc-3", 0, "1.0-rc", Some( "3" ) ),
+            ( "a:1.0", 0, "a:1.0", None ),
+        ];
+        for (evr, epoch, version, release) in table {
+            let edition = Edition::new( evr );
+            assert_eq!( ( edition.epoch(), edition.version(), edition.release() ), ( epoch, version, release ), "{}", evr );
+        }
+        assert_eq!( Edition::from_parts( 2, "1.0", Some( "3.1" ) ).as_str(), "2:1.0-3.1" );
+        assert_eq!( Edition::from_parts( 0, "1.0", None ).as_str(), "1.0" );
+    }
+}
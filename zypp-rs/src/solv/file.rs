@@ -0,0 +1,56 @@
+use solv_sys as raw;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// A C FILE handle opened through libsolv's solv_xfopen, which transparently
+/// handles compressed files. The handle is closed when this is dropped.
+pub( crate ) struct SolvFile {
+    fp: *mut raw::FILE
+}
+
+impl SolvFile {
+
+    pub fn open<P: AsRef<Path>> ( path: P, mode: &str ) -> io::Result<Self> {
+        let invalid = |_| io::Error::from( io::ErrorKind::InvalidInput );
+        let c_path = CString::new( path.as_ref().as_os_str().as_bytes() ).map_err( invalid )?;
+        let c_mode = CString::new( mode ).map_err( invalid )?;
+        let fp = unsafe { raw::solv_xfopen( c_path.as_ptr(), c_mode.as_ptr() ) };
+        if fp.is_null() {
+            return Err( io::Error::last_os_error() );
+        }
+        Ok( SolvFile { fp } )
+    }
+
+    pub fn as_ptr ( &self ) -> *mut raw::FILE {
+        self.fp
+    }
+
+    /// Closes the file, other than dropping it this reports errors that happen while flushing
+    pub fn close ( mut self ) -> io::Result<()> {
+        let fp = std::mem::replace( &mut self.fp, std::ptr::null_mut() );
+        if unsafe { raw::fclose( fp ) } != 0 {
+            return Err( io::Error::last_os_error() );
+        }
+        Ok(())
+    }
+}
+
+impl io::Read for SolvFile {
+    fn read( &mut self, buf: &mut [u8] ) -> io::Result<usize> {
+        let read = unsafe { raw::fread( buf.as_mut_ptr() as *mut _, 1, buf.len(), self.fp ) };
+        if read == 0 && unsafe { raw::ferror( self.fp ) } != 0 {
+            return Err( io::Error::last_os_error() );
+        }
+        Ok( read )
+    }
+}
+
+impl Drop for SolvFile {
+    fn drop(&mut self) {
+        if !self.fp.is_null() {
+            unsafe { raw::fclose( self.fp ); }
+        }
+    }
+}
@@ -1,2 +1,11 @@
+pub mod arch;
+pub mod edition;
 pub mod repository;
 pub mod pool;
+pub mod solvable;
+pub mod query;
+pub mod solver;
+pub mod testcase;
+pub mod transaction;
+mod queue;
+pub(crate) mod file;
@@ -1,29 +1,38 @@
 use solv_sys as raw;
+use std::cell::Cell;
 use std::ffi::{CStr,CString, NulError};
+use std::io;
 
+use std::os::raw::c_int;
+
+use crate::error::PoolError;
+use super::arch::host_arch;
+use super::query::Query;
+use super::queue::Queue;
 use super::repository::Repository;
+use super::solver::JobTarget;
+use super::solvable::Solvable;
 
 pub type Id = raw::Id;
 
 #[derive(Debug)]
 pub struct Pool {
-    pool: *mut raw::Pool
+    pub( crate ) pool: *mut raw::Pool,
+    /// set whenever solvables or the architecture change, the provides index is only
+    /// created again when it is needed and this is set
+    pub( crate ) whatprovides_dirty: Cell<bool>
 }
 
 
 impl Pool {
 
-    pub( crate ) fn new_from_ptr ( pool: *mut raw::Pool ) -> Self {
-        Pool { pool }
-    }
-
     pub fn new() -> Self {
         unsafe {
             let pool = raw::pool_create();
             if pool.is_null() {
                 panic!("Failed to init pool");
             }
-            Pool { pool }
+            Pool { pool, whatprovides_dirty: Cell::new( true ) }
         }
     }
 
@@ -54,22 +63,240 @@ impl Pool {
         }
     }
 
-    pub fn delete_repository ( &mut self, repo: Repository ) {
+    /// Sets the architecture of the system. Solvables of incompatible architectures are
+    /// ignored by the solver, libsolv knows which ones are compatible, e.g. x86_64_v3
+    /// can also use x86_64_v2, x86_64, i686 and noarch packages.
+    pub fn set_arch ( &mut self, arch: &str ) -> Result<(), NulError> {
+        let c_arch = CString::new( arch )?;
+        unsafe { raw::pool_setarch( self.pool, c_arch.as_ptr() ); }
+        self.invalidate_whatprovides();
+        Ok(())
+    }
+
+    /// Sets the architecture of the machine we are running on, see arch::host_arch
+    pub fn set_host_arch ( &mut self ) {
+        self.set_arch( &host_arch() ).expect( "Architecture names never contain a nul byte" )
+    }
+
+    /// The architectures that can be installed on the system, best first and noarch last.
+    /// Empty if no architecture was set, then solvables of all architectures are used.
+    pub fn compatible_arches ( &self ) -> Vec<String> {
+        let scores = unsafe {
+            let pool = &*self.pool;
+            if pool.id2arch.is_null() || pool.lastarch <= 0 {
+                return Vec::new();
+            }
+            std::slice::from_raw_parts( pool.id2arch, pool.lastarch as usize )
+        };
+        let mut arches: Vec<(Id, Id)> = scores.iter().enumerate()
+            .filter( |(_, score)| **score != 0 )
+            .map( |(id, score)| ( id as Id, *score ) )
+            .collect();
+        // noarch has the lowest score but is the least specific one
+        arches.sort_by_key( |(_, score)| ( *score == 1, *score ) );
+        arches.into_iter().map( |(id, _)| self.id2str( id ) ).collect()
+    }
+
+    /// True if packages of the architecture can be installed, see set_arch
+    pub fn is_compatible_arch ( &self, arch: &str ) -> bool {
+        let Ok(c_arch) = CString::new( arch ) else {
+            return false;
+        };
+        let id = unsafe { raw::pool_str2id( self.pool, c_arch.as_ptr(), 0 ) };
+        unsafe {
+            let pool = &*self.pool;
+            if pool.id2arch.is_null() {
+                // without an architecture everything is accepted
+                return true;
+            }
+            id > 0 && id < pool.lastarch && *pool.id2arch.offset( id as isize ) != 0
+        }
+    }
+
+    pub fn create_repository ( &self, name: &str ) -> Result<Repository<'_>, NulError> {
+        let c_name = CString::new( name )?;
+        unsafe {
+            let repo = raw::repo_create( self.pool, c_name.as_ptr() );
+            if repo.is_null() {
+                panic!("Failed to create repository");
+            }
+            self.invalidate_whatprovides();
+            Ok( Repository::new_from_ptr( self, repo ) )
+        }
+    }
+
+    /// Returns the repository with the given id, see Repository::id
+    pub fn repository ( &self, id: Id ) -> Option<Repository<'_>> {
         unsafe {
-            if self.pool != (*repo.repo).pool {
-                return;
+            if id <= 0 || id >= (*self.pool).nrepos {
+                return None;
             }
-            raw::repo_free( repo.repo, 0 );
-            if (*self.pool).nrepos == 0 {
+            let repo = *(*self.pool).repos.offset( id as isize );
+            if repo.is_null() {
+                return None;
+            }
+            Some( Repository::new_from_ptr( self, repo ) )
+        }
+    }
+
+    /// Iterates over all repositories in the pool
+    pub fn repositories ( &self ) -> impl Iterator<Item = Repository<'_>> {
+        let count = unsafe { (*self.pool).nrepos };
+        ( 1..count ).filter_map( move |id| self.repository( id ) )
+    }
+
+    pub fn find_repository ( &self, name: &str ) -> Option<Repository<'_>> {
+        self.repositories().find( |r| r.name() == name )
+    }
+
+    /// The last error message libsolv stored in the pool
+    pub fn error_string ( &self ) -> String {
+        unsafe { Pool::error_string_from_ptr( self.pool ) }
+    }
+
+    pub( crate ) unsafe fn error_string_from_ptr ( pool: *mut raw::Pool ) -> String {
+        Pool::str_from_ptr( raw::pool_errstr( pool ) ).unwrap_or_default()
+    }
+
+    /// Copies a string returned by libsolv, None if libsolv returned NULL
+    pub( crate ) unsafe fn str_from_ptr ( unsafe_str: *const std::os::raw::c_char ) -> Option<String> {
+        if unsafe_str.is_null() {
+            return None;
+        }
+        Some( String::from_utf8_lossy( CStr::from_ptr( unsafe_str ).to_bytes() ).to_string() )
+    }
+
+    pub( crate ) fn id2str ( &self, id: Id ) -> String {
+        unsafe { Pool::str_from_ptr( raw::pool_id2str( self.pool, id ) ).unwrap_or_default() }
+    }
+
+    /// Returns the id of a string, the string is added to the pool if it is not known yet
+    pub( crate ) fn str2id ( &self, s: &str ) -> Result<Id, NulError> {
+        let c_str = CString::new( s )?;
+        unsafe { Ok( raw::pool_str2id( self.pool, c_str.as_ptr(), 1 ) ) }
+    }
+
+    /// Parses a dependency like "foo", "foo >= 1.0" or "(foo if bar)" into its id
+    pub( crate ) fn str2dep ( &self, s: &str ) -> Result<Id, PoolError> {
+        let c_str = CString::new( s ).map_err( |_| PoolError::Io( io::Error::from( io::ErrorKind::InvalidInput ) ) )?;
+        match unsafe { raw::testcase_str2dep( self.pool, c_str.as_ptr() ) } {
+            0 => Err( PoolError::Libsolv( format!("Invalid dependency: {}", s) ) ),
+            id => Ok( id )
+        }
+    }
+
+    /// Formats a dependency id, including relations and rich dependencies
+    pub( crate ) fn dep2str ( &self, id: Id ) -> String {
+        unsafe { Pool::str_from_ptr( raw::pool_dep2str( self.pool, id ) ).unwrap_or_default() }
+    }
+
+    /// Marks the repository as the one holding the installed packages, usually @System
+    pub fn set_installed ( &self, repo: &Repository ) {
+        unsafe {
+            assert!( (*repo.repo).pool == self.pool, "The repository belongs to a different pool" );
+            raw::pool_set_installed( self.pool, repo.repo );
+        }
+        self.invalidate_whatprovides();
+    }
+
+    /// The repository holding the installed packages, see set_installed
+    pub fn installed ( &self ) -> Option<Repository<'_>> {
+        let repo = unsafe { (*self.pool).installed };
+        if repo.is_null() {
+            return None;
+        }
+        Some( Repository::new_from_ptr( self, repo ) )
+    }
+
+    /// Frees the repository with the given id together with all its solvables. This needs
+    /// exclusive access to the pool, so it can not be called while any Repository or
+    /// Solvable handle is still around.
+    pub fn delete_repository ( &mut self, id: Id ) {
+        let Some(repo) = self.repository( id ).map( |r| r.repo ) else {
+            return;
+        };
+        unsafe {
+            raw::repo_free( repo, 0 );
+            if (*self.pool).urepos == 0 {
                 raw::pool_freeallrepos( self.pool, 1 );
             }
         }
+        self.invalidate_whatprovides();
+    }
+
+    /// Creates the index of which solvables provide what, this has to be done after all
+    /// repositories were loaded and before solving or searching for dependencies.
+    /// select, what_provides and the solver do it themselves when the pool changed.
+    pub fn create_whatprovides ( &self ) {
+        unsafe {
+            raw::pool_addfileprovides( self.pool );
+            raw::pool_createwhatprovides( self.pool );
+        }
+        self.whatprovides_dirty.set( false );
+    }
+
+    /// Creates the provides index again if solvables were added or removed since it was
+    /// created last. Ids of libsolv's whatprovides lists, like the ones in a selection,
+    /// stay valid as long as the index is not created again.
+    pub( crate ) fn ensure_whatprovides ( &self ) {
+        if self.whatprovides_dirty.get() {
+            self.create_whatprovides();
+        }
+    }
+
+    /// Marks the provides index as outdated, see ensure_whatprovides
+    pub( crate ) fn invalidate_whatprovides ( &self ) {
+        self.whatprovides_dirty.set( true );
+    }
+
+    /// Returns the solvable with the given id, None if there is no such solvable
+    pub fn solvable ( &self, id: Id ) -> Option<Solvable<'_>> {
+        unsafe {
+            if id <= 1 || id >= (*self.pool).nsolvables {
+                return None;
+            }
+            if (*(*self.pool).solvables.offset( id as isize )).repo.is_null() {
+                return None;
+            }
+        }
+        Some( Solvable::new( self, id ) )
+    }
+
+    /// Iterates over all solvables in all repositories of the pool
+    pub fn solvables ( &self ) -> impl Iterator<Item = Solvable<'_>> {
+        let count = unsafe { (*self.pool).nsolvables };
+        ( 2..count ).filter_map( move |id| self.solvable( id ) )
+    }
+
+    /// Returns the solvables matching the query, sorted by id
+    pub fn search ( &self, query: &Query ) -> Result<Vec<Solvable<'_>>, PoolError> {
+        query.run( self )
+    }
+
+    /// Turns the query into a job target, like zypper does with the arguments of install
+    /// or remove. Unlike search, the target keeps libsolv's selection of names and
+    /// capabilities, so installing "foo" picks the best foo instead of every found version.
+    /// Only the name, provides and files fields can be selected, and no regular expressions.
+    /// Fails if nothing matches.
+    pub fn select ( &self, query: &Query ) -> Result<JobTarget, PoolError> {
+        self.ensure_whatprovides();
+        Ok( JobTarget::Selection( query.selection( self )? ) )
     }
 
-    pub fn add_solvable ( &mut self ) -> Id {
+    /// Returns the solvables providing a capability, like "bash", "perl >= 5.30" or a
+    /// file like "/usr/bin/sh". The provides index is created again if repositories
+    /// were loaded since, so this works right after loading them.
+    pub fn what_provides ( &self, capability: &str ) -> Result<Vec<Solvable<'_>>, NulError> {
+        let c_capability = CString::new( capability )?;
+        self.ensure_whatprovides();
+        let flags = raw::SELECTION_PROVIDES | raw::SELECTION_FILELIST | raw::SELECTION_REL;
+        let mut selection = Queue::new();
+        let mut ids = Queue::new();
         unsafe {
-            return raw::pool_add_solvable( self.pool );
+            raw::selection_make( self.pool, selection.as_mut_ptr(), c_capability.as_ptr(), flags as c_int );
+            raw::selection_solvables( self.pool, selection.as_mut_ptr(), ids.as_mut_ptr() );
         }
+        Ok( ids.as_slice().iter().filter_map( |id| self.solvable( *id ) ).collect() )
     }
 }
 
@@ -78,3 +305,54 @@ impl Drop for Pool {
         unsafe { raw::pool_free( self.pool ); }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::query::MatchMode;
+
+    #[test]
+    fn provides_index_is_only_created_after_changes () {
+        let mut pool = Pool::new();
+        assert!( pool.whatprovides_dirty.get() );
+        assert!( pool.what_provides( "foo" ).unwrap().is_empty() );
+        assert!( !pool.whatprovides_dirty.get() );
+        pool.select( &Query::new( "foo", MatchMode::Exact ) ).unwrap_err();
+        assert!( !pool.whatprovides_dirty.get() );
+
+        let id = {
+            let mut repo = pool.create_repository( "test" ).unwrap();
+            pool.create_whatprovides();
+            repo.add_solvable();
+            assert!( pool.whatprovides_dirty.get() );
+            repo.id()
+        };
+        pool.what_provides( "foo" ).unwrap();
+        assert!( !pool.whatprovides_dirty.get() );
+
+        pool.delete_repository( id );
+        assert!( pool.whatprovides_dirty.get() );
+        pool.what_provides( "foo" ).unwrap();
+        pool.set_host_arch();
+        assert!( pool.whatprovides_dirty.get() );
+    }
+
+    #[test]
+    fn repositories_are_found_by_id_and_name () {
+        let mut pool = Pool::new();
+        let ( first, second ) = ( pool.create_repository( "first" ).unwrap().id(), pool.create_repository( "second" ).unwrap().id() );
+        let names = |pool: &Pool| pool.repositories().map( |r| r.name() ).collect::<Vec<_>>();
+        assert_eq!( names( &pool ), vec![ "first", "second" ] );
+        assert_eq!( pool.repository( second ).map( |r| r.name() ).as_deref(), Some( "second" ) );
+
+        pool.delete_repository( first );
+        assert!( pool.repository( first ).is_none() );
+        assert_eq!( names( &pool ), vec![ "second" ] );
+        assert_eq!( pool.find_repository( "second" ).map( |r| r.id() ), Some( second ) );
+        assert!( pool.find_repository( "first" ).is_none() );
+
+        pool.delete_repository( second );
+        assert!( names( &pool ).is_empty() );
+        assert_eq!( pool.create_repository( "third" ).unwrap().id(), 1 );
+    }
+}
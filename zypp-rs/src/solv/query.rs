@@ -0,0 +1,189 @@
+use solv_sys as raw;
+use std::collections::BTreeSet;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::raw::c_int;
+
+use crate::error::PoolError;
+use super::pool::{Id, Pool};
+use super::queue::Queue;
+use super::solvable::{Solvable, SolvableKind};
+
+/// How the search term is compared to the searched fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    Exact,
+    Substring,
+    /// shell style wildcards like "lib*-devel"
+    Glob,
+    /// POSIX extended regular expressions
+    Regex,
+}
+
+/// The data of a solvable a query looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    /// the name without the kind prefix, so "base" matches "pattern:base"
+    Name,
+    Summary,
+    Description,
+    /// the names of the provided capabilities
+    Provides,
+    /// the paths in the file list
+    Files,
+}
+
+/// A search over the solvables of a Pool, see Pool::search. A solvable is found if the
+/// term matches in any of the fields and it passes all of the filters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    pub term: String,
+    pub mode: MatchMode,
+    pub fields: Vec<SearchField>,
+    pub case_sensitive: bool,
+    /// ids of the repositories to search, all repositories if empty
+    pub repositories: Vec<Id>,
+    /// the kinds of solvables to return, all kinds if empty
+    pub kinds: Vec<SolvableKind>,
+    /// only return installed solvables if true, only not installed ones if false
+    pub installed: Option<bool>,
+}
+
+impl Query {
+
+    /// A case insensitive query on the name
+    pub fn new ( term: &str, mode: MatchMode ) -> Self {
+        Self {
+            term: term.to_owned(),
+            mode,
+            fields: vec![ SearchField::Name ],
+            case_sensitive: false,
+            repositories: Vec::new(),
+            kinds: Vec::new(),
+            installed: None
+        }
+    }
+
+    pub( crate ) fn run<'a> ( &self, pool: &'a Pool ) -> Result<Vec<Solvable<'a>>, PoolError> {
+        let c_term = CString::new( self.term.as_str() )
+            .map_err( |_| PoolError::Io( io::Error::from( io::ErrorKind::InvalidInput ) ) )?;
+        let mut ids = BTreeSet::new();
+        for field in self.fields.iter() {
+            self.search_field( pool, *field, &c_term, &mut ids )?;
+        }
+        Ok( ids.into_iter()
+            .filter_map( |id| pool.solvable( id ) )
+            .filter( |s| self.accepts( s ) )
+            .collect() )
+    }
+
+    /// Makes a libsolv selection of the term and limits it to the solvables passing the
+    /// filters, returns the how and what pairs of the selection
+    pub( crate ) fn selection ( &self, pool: &Pool ) -> Result<Vec<(Id, Id)>, PoolError> {
+        let mut flags = 0;
+        for field in self.fields.iter() {
+            flags |= match field {
+                SearchField::Name => raw::SELECTION_NAME | raw::SELECTION_SKIP_KIND | raw::SELECTION_DOTARCH | raw::SELECTION_REL,
+                SearchField::Provides => raw::SELECTION_PROVIDES | raw::SELECTION_REL,
+                SearchField::Files => raw::SELECTION_FILELIST,
+                SearchField::Summary | SearchField::Description => {
+                    return Err( PoolError::Libsolv( format!("Can not select solvables by {:?}", field) ) );
+                }
+            };
+        }
+        let term = match self.mode {
+            MatchMode::Exact => self.term.clone(),
+            MatchMode::Substring => {
+                flags |= raw::SELECTION_GLOB;
+                format!("*{}*", self.term)
+            },
+            MatchMode::Glob => {
+                flags |= raw::SELECTION_GLOB;
+                self.term.clone()
+            },
+            MatchMode::Regex => return Err( PoolError::Libsolv( "Can not select solvables by a regular expression".to_string() ) ),
+        };
+        if !self.case_sensitive {
+            flags |= raw::SELECTION_NOCASE;
+        }
+        let c_term = CString::new( term )
+            .map_err( |_| PoolError::Io( io::Error::from( io::ErrorKind::InvalidInput ) ) )?;
+
+        let mut selection = Queue::new();
+        unsafe {
+            raw::selection_make( pool.pool, selection.as_mut_ptr(), c_term.as_ptr(), flags as c_int );
+        }
+        if !self.repositories.is_empty() || !self.kinds.is_empty() || self.installed.is_some() {
+            let mut found = Queue::new();
+            unsafe { raw::selection_solvables( pool.pool, selection.as_mut_ptr(), found.as_mut_ptr() ) };
+            let mut accepted = Queue::new();
+            found.as_slice().iter()
+                .filter_map( |id| pool.solvable( *id ) )
+                .filter( |s| self.accepts( s ) )
+                .for_each( |s| accepted.push( s.id() ) );
+            if accepted.as_slice().len() != found.as_slice().len() {
+                let mut filter = Queue::new();
+                filter.push( raw::SOLVER_SOLVABLE_ONE_OF as Id );
+                filter.push( unsafe { raw::pool_queuetowhatprovides( pool.pool, accepted.as_mut_ptr() ) } );
+                unsafe { raw::selection_filter( pool.pool, selection.as_mut_ptr(), filter.as_mut_ptr() ) };
+            }
+        }
+        if selection.as_slice().is_empty() {
+            return Err( PoolError::Libsolv( format!("Nothing matches {}", self.term) ) );
+        }
+        Ok( selection.as_slice().chunks_exact( 2 ).map( |pair| ( pair[0], pair[1] ) ).collect() )
+    }
+
+    /// Runs a libsolv dataiterator over one key of all solvables and collects the ids of the matches
+    fn search_field ( &self, pool: &Pool, field: SearchField, term: &CStr, ids: &mut BTreeSet<Id> ) -> Result<(), PoolError> {
+        let (key, field_flags) = match field {
+            SearchField::Name => ( raw::solv_knownid_SOLVABLE_NAME, raw::SEARCH_SKIP_KIND ),
+            SearchField::Summary => ( raw::solv_knownid_SOLVABLE_SUMMARY, 0 ),
+            SearchField::Description => ( raw::solv_knownid_SOLVABLE_DESCRIPTION, 0 ),
+            SearchField::Provides => ( raw::solv_knownid_SOLVABLE_PROVIDES, 0 ),
+            SearchField::Files => ( raw::solv_knownid_SOLVABLE_FILELIST, raw::SEARCH_FILES ),
+        };
+        let mut flags = field_flags | match self.mode {
+            MatchMode::Exact => raw::SEARCH_STRING,
+            MatchMode::Substring => raw::SEARCH_SUBSTRING,
+            MatchMode::Glob => raw::SEARCH_GLOB,
+            MatchMode::Regex => raw::SEARCH_REGEX,
+        };
+        if !self.case_sensitive {
+            flags |= raw::SEARCH_NOCASE;
+        }
+
+        unsafe {
+            let mut di = std::mem::MaybeUninit::<raw::Dataiterator>::uninit();
+            let di = di.as_mut_ptr();
+            if raw::dataiterator_init( di, pool.pool, std::ptr::null_mut(), 0, key as Id, term.as_ptr(), flags as c_int ) != 0 {
+                raw::dataiterator_free( di );
+                return Err( PoolError::Libsolv( format!("Invalid search term: {}", self.term) ) );
+            }
+            while raw::dataiterator_step( di ) != 0 {
+                ids.insert( (*di).solvid );
+                raw::dataiterator_skip_solvable( di );
+            }
+            raw::dataiterator_free( di );
+        }
+        Ok(())
+    }
+
+    fn accepts ( &self, solvable: &Solvable ) -> bool {
+        if !self.repositories.is_empty() {
+            let Some(repo) = solvable.repository() else {
+                return false;
+            };
+            if !self.repositories.contains( &repo.id() ) {
+                return false;
+            }
+        }
+        if !self.kinds.is_empty() && !self.kinds.contains( &solvable.kind() ) {
+            return false;
+        }
+        match self.installed {
+            Some(installed) => solvable.is_installed() == installed,
+            None => true
+        }
+    }
+}
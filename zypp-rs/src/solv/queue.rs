@@ -0,0 +1,41 @@
+use solv_sys as raw;
+
+use super::pool::Id;
+
+/// Owned libsolv Queue, the id array libsolv uses to pass lists in and out of most functions.
+/// The memory is released with queue_free when this is dropped.
+pub( crate ) struct Queue {
+    q: raw::Queue
+}
+
+impl Queue {
+
+    pub fn new () -> Self {
+        unsafe {
+            let mut q = std::mem::MaybeUninit::<raw::Queue>::uninit();
+            raw::queue_init( q.as_mut_ptr() );
+            Queue { q: q.assume_init() }
+        }
+    }
+
+    pub fn as_mut_ptr ( &mut self ) -> *mut raw::Queue {
+        &mut self.q
+    }
+
+    pub fn push ( &mut self, id: Id ) {
+        unsafe { raw::queue_insert( &mut self.q, self.q.count, id ); }
+    }
+
+    pub fn as_slice ( &self ) -> &[Id] {
+        if self.q.count <= 0 || self.q.elements.is_null() {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts( self.q.elements, self.q.count as usize ) }
+    }
+}
+
+impl Drop for Queue {
+    fn drop(&mut self) {
+        unsafe { raw::queue_free( &mut self.q ); }
+    }
+}
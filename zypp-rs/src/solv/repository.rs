@@ -1,21 +1,210 @@
 use solv_sys as raw;
-//use std::ffi::{CStr,CString, NulError};
-use super::pool::Pool;
+use std::ffi::CString;
+use std::io;
+use std::os::raw::c_int;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use crate::error::PoolError;
+use super::file::SolvFile;
+use super::pool::{Id, Pool};
+use super::solvable::Solvable;
 
+/// A repository inside a Pool. The handle borrows the pool, the repository itself is
+/// owned by the pool and only freed through Pool::delete_repository, which needs
+/// exclusive access to the pool, so no handle can ever point to a freed repository.
 #[derive(Debug)]
-pub struct Repository {
+pub struct Repository<'a> {
+    pool: &'a Pool,
     pub( crate ) repo: *mut raw::Repo
 }
 
-impl Repository {
+impl<'a> Repository<'a> {
 
-    pub( crate ) fn new_from_ptr ( repo: *mut raw::Repo ) -> Self {
-        return Repository { repo }
+    pub( crate ) fn new_from_ptr ( pool: &'a Pool, repo: *mut raw::Repo ) -> Self {
+        Repository { pool, repo }
     }
 
-    pub fn get_pool ( &self ) -> Pool {
+    /// The pool this repository belongs to
+    pub fn pool ( &self ) -> &'a Pool {
+        self.pool
+    }
+
+    /// The id of the repository in its pool, see Pool::repository
+    pub fn id ( &self ) -> Id {
+        unsafe { (*self.repo).repoid }
+    }
+
+    pub fn name ( &self ) -> String {
+        unsafe { Pool::str_from_ptr( (*self.repo).name ).unwrap_or_default() }
+    }
+
+    /// Number of solvables in this repository
+    pub fn len ( &self ) -> usize {
+        unsafe { (*self.repo).nsolvables as usize }
+    }
+
+    pub fn is_empty ( &self ) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over all solvables of this repository
+    pub fn solvables ( &self ) -> impl Iterator<Item = Solvable<'a>> + '_ {
+        let (start, end) = unsafe { ( (*self.repo).start, (*self.repo).end ) };
+        let pool = self.pool;
+        ( start..end ).filter_map( move |id| pool.solvable( id ).filter( |s| s.repo_ptr() == self.repo ) )
+    }
+
+    /// Adds a new empty solvable to this repository
+    pub fn add_solvable ( &mut self ) -> Solvable<'a> {
+        let id = unsafe { raw::repo_add_solvable( self.repo ) };
+        self.pool.invalidate_whatprovides();
+        Solvable::new( self.pool, id )
+    }
+
+    fn last_error ( &self ) -> PoolError {
+        PoolError::Libsolv( self.pool.error_string() )
+    }
+
+    /// Opens the file through solv_xfopen, so compressed files work as well, and hands it
+    /// to one of libsolv's repo_add_* readers
+    fn add_file<P, F> ( &mut self, file_path: P, add: F ) -> Result<(), PoolError>
+        where P: AsRef<Path>, F: FnOnce( *mut raw::Repo, *mut raw::FILE ) -> c_int {
+        let file = SolvFile::open( file_path, "r" )?;
+        // readers may have added solvables even if they fail
+        self.pool.invalidate_whatprovides();
+        if add( self.repo, file.as_ptr() ) != 0 {
+            return Err( self.last_error() );
+        }
+        Ok(())
+    }
+
+    /// Adds all solvables from a .solv file to this repository
+    pub fn add_solv<P: AsRef<Path>> ( &mut self, file_path: P ) -> Result<(), PoolError> {
+        self.add_file( file_path, |repo, fp| unsafe { raw::repo_add_solv( repo, fp, 0 ) } )
+    }
+
+    /// Reads the repository meta data like the revision and keywords from a rpm-md repomd.xml
+    pub fn add_repomdxml<P: AsRef<Path>> ( &mut self, file_path: P ) -> Result<(), PoolError> {
+        self.add_file( file_path, |repo, fp| unsafe { raw::repo_add_repomdxml( repo, fp, 0 ) } )
+    }
+
+    /// Adds the packages of a rpm-md primary.xml, the file may be compressed
+    pub fn add_rpmmd<P: AsRef<Path>> ( &mut self, file_path: P, language: Option<&str> ) -> Result<(), PoolError> {
+        self.add_rpmmd_flags( file_path, language, 0 )
+    }
+
+    /// Adds the data of filelists.xml, other.xml or susedata.xml to the packages that
+    /// were added from primary.xml before
+    pub fn extend_rpmmd<P: AsRef<Path>> ( &mut self, file_path: P, language: Option<&str> ) -> Result<(), PoolError> {
+        self.add_rpmmd_flags( file_path, language, raw::REPO_EXTEND_SOLVABLES as c_int )
+    }
+
+    fn add_rpmmd_flags<P: AsRef<Path>> ( &mut self, file_path: P, language: Option<&str>, flags: c_int ) -> Result<(), PoolError> {
+        let c_language = language.map( CString::new ).transpose()
+            .map_err( |_| PoolError::Io( io::Error::from( io::ErrorKind::InvalidInput ) ) )?;
+        let language_ptr = c_language.as_ref().map_or( std::ptr::null(), |l| l.as_ptr() );
+        self.add_file( file_path, |repo, fp| unsafe { raw::repo_add_rpmmd( repo, fp, language_ptr, flags ) } )
+    }
+
+    /// Adds the patches of a rpm-md updateinfo.xml
+    pub fn add_updateinfoxml<P: AsRef<Path>> ( &mut self, file_path: P ) -> Result<(), PoolError> {
+        self.add_file( file_path, |repo, fp| unsafe { raw::repo_add_updateinfoxml( repo, fp, 0 ) } )
+    }
+
+    /// Reads the product data from the content file of a susetags repository
+    pub fn add_content<P: AsRef<Path>> ( &mut self, file_path: P ) -> Result<(), PoolError> {
+        self.add_file( file_path, |repo, fp| unsafe { raw::repo_add_content( repo, fp, 0 ) } )
+    }
+
+    /// Adds the packages of a susetags packages file, or of a .pat pattern file. Packages
+    /// that do not specify a vendor get the default vendor from the content file.
+    pub fn add_susetags<P: AsRef<Path>> ( &mut self, file_path: P, default_vendor: Option<&str>, language: Option<&str> ) -> Result<(), PoolError> {
+        self.add_susetags_flags( file_path, default_vendor, language, raw::SUSETAGS_RECORD_SHARES as c_int )
+    }
+
+    /// Adds the data of a translated packages file like packages.en to the packages that
+    /// were added from the packages file before
+    pub fn extend_susetags<P: AsRef<Path>> ( &mut self, file_path: P, default_vendor: Option<&str>, language: Option<&str> ) -> Result<(), PoolError> {
+        self.add_susetags_flags( file_path, default_vendor, language, raw::REPO_EXTEND_SOLVABLES as c_int )
+    }
+
+    fn add_susetags_flags<P: AsRef<Path>> ( &mut self, file_path: P, default_vendor: Option<&str>, language: Option<&str>, flags: c_int ) -> Result<(), PoolError> {
+        let invalid = |_| PoolError::Io( io::Error::from( io::ErrorKind::InvalidInput ) );
+        let vendor = default_vendor.map( |v| self.pool.str2id( v ) ).transpose().map_err( invalid )?.unwrap_or( 0 );
+        let c_language = language.map( CString::new ).transpose().map_err( invalid )?;
+        let language_ptr = c_language.as_ref().map_or( std::ptr::null(), |l| l.as_ptr() );
+        self.add_file( file_path, |repo, fp| unsafe { raw::repo_add_susetags( repo, fp, vendor, language_ptr, flags ) } )
+    }
+
+    /// Reads the header of a rpm file and adds the package to this repository. The location
+    /// is the path of the rpm relative to the repository root, it is used to download the
+    /// package later. Call internalize once all rpms were added.
+    pub fn add_rpm<P: AsRef<Path>> ( &mut self, rpm_path: P, location: &str ) -> Result<Solvable<'a>, PoolError> {
+        let invalid = |_| PoolError::Io( io::Error::from( io::ErrorKind::InvalidInput ) );
+        let c_path = CString::new( rpm_path.as_ref().as_os_str().as_bytes() ).map_err( invalid )?;
+        let c_location = CString::new( location ).map_err( invalid )?;
+        self.pool.invalidate_whatprovides();
+        let flags = raw::REPO_REUSE_REPODATA | raw::REPO_NO_INTERNALIZE | raw::REPO_NO_LOCATION
+            | raw::RPM_ADD_WITH_PKGID | raw::RPM_ADD_WITH_SHA256SUM;
+        unsafe {
+            let id = raw::repo_add_rpm( self.repo, c_path.as_ptr(), flags as c_int );
+            if id == 0 {
+                return Err( self.last_error() );
+            }
+            raw::repodata_set_location( raw::repo_last_repodata( self.repo ), id, 0, std::ptr::null(), c_location.as_ptr() );
+            Ok( Solvable::new( self.pool, id ) )
+        }
+    }
+
+    /// Reads the installed packages from the rpm database below the root directory of the pool.
+    /// Headers of packages that are also in the reference .solv file, usually the previous
+    /// cache of the rpm database, are taken from there instead of being read again.
+    pub fn add_rpmdb<P: AsRef<Path>> ( &mut self, reference: Option<P> ) -> Result<(), PoolError> {
+        let flags = ( raw::REPO_REUSE_REPODATA | raw::REPO_USE_ROOTDIR | raw::RPM_ADD_WITH_HDRID ) as c_int;
+        match reference {
+            Some(reference) => self.add_file( reference, |repo, fp| unsafe { raw::repo_add_rpmdb_reffp( repo, fp, flags ) } ),
+            None => {
+                self.pool.invalidate_whatprovides();
+                if unsafe { raw::repo_add_rpmdb( self.repo, std::ptr::null_mut(), flags ) } != 0 {
+                    return Err( self.last_error() );
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Makes data that was added with REPO_NO_INTERNALIZE, like by add_rpm, available for lookups
+    pub fn internalize ( &mut self ) {
+        unsafe { raw::repo_internalize( self.repo ); }
+        self.pool.invalidate_whatprovides();
+    }
+
+    /// Creates pattern solvables for the pattern-* packages in this repository, like repo2solv -X
+    pub fn add_autopattern ( &mut self ) -> Result<(), PoolError> {
+        self.pool.invalidate_whatprovides();
+        if unsafe { raw::repo_add_autopattern( self.repo, 0 ) } != 0 {
+            return Err( self.last_error() );
+        }
+        Ok(())
+    }
+
+    /// Writes the repository as .solv file
+    pub fn write_solv<P: AsRef<Path>> ( &self, file_path: P ) -> Result<(), PoolError> {
+        let file = SolvFile::open( file_path, "w" )?;
+        unsafe {
+            if raw::repo_write( self.repo, file.as_ptr() ) != 0 {
+                return Err( self.last_error() );
+            }
+        }
+        file.close()?;
+        Ok(())
+    }
+
+    /// libsolv prefers solvables from repositories with a higher priority, so the zypp
+    /// priority, where lower numbers win, needs to be inverted.
+    pub fn set_zypp_priority ( &mut self, priority: u32 ) {
         unsafe {
-            return Pool::new_from_ptr( (*self.repo).pool );
+            (*self.repo).priority = crate::repoinfo::DEFAULT_PRIORITY as i32 - priority as i32;
         }
     }
 }
@@ -0,0 +1,356 @@
+use solv_sys as raw;
+use std::fmt;
+
+use crate::checksum::{CheckSum, CheckSumType};
+use super::edition::Edition;
+use super::pool::{Id, Pool};
+use super::queue::Queue;
+use super::repository::Repository;
+
+/// The comparison of a versioned dependency like "bash >= 5.0"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+    Ne,
+}
+
+impl Relation {
+    fn from_flags ( flags: u32 ) -> Option<Self> {
+        match flags {
+            f if f == raw::REL_LT => Some(Relation::Lt),
+            f if f == raw::REL_LT | raw::REL_EQ => Some(Relation::Le),
+            f if f == raw::REL_EQ => Some(Relation::Eq),
+            f if f == raw::REL_GT | raw::REL_EQ => Some(Relation::Ge),
+            f if f == raw::REL_GT => Some(Relation::Gt),
+            f if f == raw::REL_LT | raw::REL_GT => Some(Relation::Ne),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Relation::Lt => "<",
+            Relation::Le => "<=",
+            Relation::Eq => "=",
+            Relation::Ge => ">=",
+            Relation::Gt => ">",
+            Relation::Ne => "!=",
+        };
+        f.write_str(op)
+    }
+}
+
+/// One entry of a dependency list, e.g. a single provides or requires
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dependency {
+    /// unversioned dependency, e.g. "bash" or "/usr/bin/sh"
+    Name(String),
+    /// dependency on a name with a version relation, e.g. "bash >= 5.0"
+    Versioned {
+        name: String,
+        op: Relation,
+        evr: String
+    },
+    /// rich dependencies, namespaces and everything else, as libsolv prints it
+    Complex(String),
+}
+
+fn is_reldep ( id: Id ) -> bool {
+    ( id as u32 ) & 0x8000_0000 != 0
+}
+
+impl Dependency {
+
+    pub( crate ) fn from_id ( pool: &Pool, id: Id ) -> Self {
+        if !is_reldep( id ) {
+            return Dependency::Name( pool.id2str( id ) );
+        }
+        let (name, evr, flags) = unsafe {
+            let rd = (*pool.pool).rels.offset( ( id as u32 ^ 0x8000_0000 ) as isize );
+            ( (*rd).name, (*rd).evr, (*rd).flags )
+        };
+        if !is_reldep( name ) && !is_reldep( evr ) {
+            if let Some(op) = Relation::from_flags( flags as u32 ) {
+                return Dependency::Versioned { name: pool.id2str( name ), op, evr: pool.id2str( evr ) };
+            }
+        }
+        Dependency::Complex( pool.dep2str( id ) )
+    }
+
+    /// The name the dependency refers to, for complex dependencies the whole expression
+    pub fn name ( &self ) -> &str {
+        match self {
+            Dependency::Name(name) | Dependency::Versioned { name, .. } | Dependency::Complex(name) => name
+        }
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dependency::Name(name) | Dependency::Complex(name) => f.write_str(name),
+            Dependency::Versioned { name, op, evr } => write!( f, "{} {} {}", name, op, evr )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyKind {
+    Provides,
+    Requires,
+    Conflicts,
+    Obsoletes,
+    Recommends,
+    Suggests,
+    Supplements,
+    Enhances,
+}
+
+impl DependencyKind {
+    fn key ( &self ) -> Id {
+        let key = match self {
+            DependencyKind::Provides => raw::solv_knownid_SOLVABLE_PROVIDES,
+            DependencyKind::Requires => raw::solv_knownid_SOLVABLE_REQUIRES,
+            DependencyKind::Conflicts => raw::solv_knownid_SOLVABLE_CONFLICTS,
+            DependencyKind::Obsoletes => raw::solv_knownid_SOLVABLE_OBSOLETES,
+            DependencyKind::Recommends => raw::solv_knownid_SOLVABLE_RECOMMENDS,
+            DependencyKind::Suggests => raw::solv_knownid_SOLVABLE_SUGGESTS,
+            DependencyKind::Supplements => raw::solv_knownid_SOLVABLE_SUPPLEMENTS,
+            DependencyKind::Enhances => raw::solv_knownid_SOLVABLE_ENHANCES,
+        };
+        key as Id
+    }
+}
+
+/// Where the package file can be found on the repository medium
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadLocation {
+    /// path of the file, relative to the repository root
+    pub path: String,
+    /// number of the medium the file is on, for multi media repositories
+    pub medianr: u32
+}
+
+/// The kind of a solvable, libsolv encodes it as prefix of the name like "pattern:base"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SolvableKind {
+    Package,
+    Pattern,
+    Patch,
+    Product,
+    /// a source package, its arch is src or nosrc
+    SrcPackage,
+}
+
+/// A package, pattern, product or any other solvable in a Pool.
+/// The handle borrows the pool, so it can neither outlive the pool nor the repository
+/// it belongs to, see Pool::delete_repository.
+#[derive(Clone, Copy)]
+pub struct Solvable<'a> {
+    pool: &'a Pool,
+    id: Id
+}
+
+impl<'a> Solvable<'a> {
+
+    pub( crate ) fn new ( pool: &'a Pool, id: Id ) -> Self {
+        Solvable { pool, id }
+    }
+
+    fn as_ptr ( &self ) -> *mut raw::Solvable {
+        unsafe { (*self.pool.pool).solvables.offset( self.id as isize ) }
+    }
+
+    pub( crate ) fn repo_ptr ( &self ) -> *mut raw::Repo {
+        unsafe { (*self.as_ptr()).repo }
+    }
+
+    pub fn id ( &self ) -> Id {
+        self.id
+    }
+
+    /// True if the solvable is part of the installed repository, see Pool::set_installed
+    pub fn is_installed ( &self ) -> bool {
+        let repo = self.repo_ptr();
+        !repo.is_null() && repo == unsafe { (*self.pool.pool).installed }
+    }
+
+    /// The repository this solvable belongs to
+    pub fn repository ( &self ) -> Option<Repository<'a>> {
+        let repo = self.repo_ptr();
+        if repo.is_null() {
+            return None;
+        }
+        Some( Repository::new_from_ptr( self.pool, repo ) )
+    }
+
+    pub fn name ( &self ) -> String {
+        self.pool.id2str( unsafe { (*self.as_ptr()).name } )
+    }
+
+    /// The edition as "epoch:version-release", the epoch is omitted if it is not set
+    pub fn evr ( &self ) -> String {
+        self.pool.id2str( unsafe { (*self.as_ptr()).evr } )
+    }
+
+    /// The edition, which compares like rpm versions
+    pub fn edition ( &self ) -> Edition {
+        Edition::new( &self.evr() )
+    }
+
+    pub fn arch ( &self ) -> String {
+        self.pool.id2str( unsafe { (*self.as_ptr()).arch } )
+    }
+
+    pub fn kind ( &self ) -> SolvableKind {
+        let name = self.name();
+        match name.split_once( ':' ).map( |(kind, _)| kind ) {
+            Some("pattern") => SolvableKind::Pattern,
+            Some("patch") => SolvableKind::Patch,
+            Some("product") => SolvableKind::Product,
+            _ if matches!( self.arch().as_str(), "src" | "nosrc" ) => SolvableKind::SrcPackage,
+            _ => SolvableKind::Package,
+        }
+    }
+
+    pub fn vendor ( &self ) -> Option<String> {
+        let vendor = unsafe { (*self.as_ptr()).vendor };
+        if vendor == 0 {
+            return None;
+        }
+        Some( self.pool.id2str( vendor ) )
+    }
+
+    fn lookup_str ( &self, key: Id ) -> Option<String> {
+        unsafe {
+            let unsafe_str = raw::solvable_lookup_str( self.as_ptr(), key );
+            Pool::str_from_ptr( unsafe_str )
+        }
+    }
+
+    pub fn summary ( &self ) -> Option<String> {
+        self.lookup_str( raw::solv_knownid_SOLVABLE_SUMMARY as Id )
+    }
+
+    pub fn description ( &self ) -> Option<String> {
+        self.lookup_str( raw::solv_knownid_SOLVABLE_DESCRIPTION as Id )
+    }
+
+    pub fn location ( &self ) -> Option<DownloadLocation> {
+        let mut medianr: std::os::raw::c_uint = 0;
+        let path = unsafe {
+            Pool::str_from_ptr( raw::solvable_lookup_location( self.as_ptr(), &mut medianr ) )?
+        };
+        Some( DownloadLocation { path, medianr } )
+    }
+
+    /// The checksum of the package file, None if the repository did not provide one
+    /// or libsolv uses a checksum type we do not support.
+    pub fn checksum ( &self ) -> Option<CheckSum> {
+        let mut type_id: Id = 0;
+        let value = unsafe {
+            Pool::str_from_ptr( raw::solvable_lookup_checksum( self.as_ptr(), raw::solv_knownid_SOLVABLE_CHECKSUM as Id, &mut type_id ) )?
+        };
+        let kind = match type_id as u32 {
+            t if t == raw::solv_knownid_REPOKEY_TYPE_MD5 => CheckSumType::Md5,
+            t if t == raw::solv_knownid_REPOKEY_TYPE_SHA1 => CheckSumType::Sha1,
+            t if t == raw::solv_knownid_REPOKEY_TYPE_SHA224 => CheckSumType::Sha224,
+            t if t == raw::solv_knownid_REPOKEY_TYPE_SHA256 => CheckSumType::Sha256,
+            t if t == raw::solv_knownid_REPOKEY_TYPE_SHA384 => CheckSumType::Sha384,
+            t if t == raw::solv_knownid_REPOKEY_TYPE_SHA512 => CheckSumType::Sha512,
+            _ => return None
+        };
+        CheckSum::new( kind, &value ).ok()
+    }
+
+    fn lookup_num ( &self, key: Id ) -> u64 {
+        unsafe { raw::solvable_lookup_num( self.as_ptr(), key, 0 ) as u64 }
+    }
+
+    /// Size of the package file in bytes, 0 if unknown
+    pub fn download_size ( &self ) -> u64 {
+        self.lookup_num( raw::solv_knownid_SOLVABLE_DOWNLOADSIZE as Id )
+    }
+
+    /// Size of the installed package in bytes, 0 if unknown
+    pub fn install_size ( &self ) -> u64 {
+        self.lookup_num( raw::solv_knownid_SOLVABLE_INSTALLSIZE as Id )
+    }
+
+    fn lookup_deps ( &self, key: Id, marker: Id ) -> Vec<Dependency> {
+        let markers = [ raw::solv_knownid_SOLVABLE_PREREQMARKER as Id, raw::solv_knownid_SOLVABLE_FILEMARKER as Id ];
+        let mut q = Queue::new();
+        unsafe { raw::solvable_lookup_deparray( self.as_ptr(), key, q.as_mut_ptr(), marker ); }
+        q.as_slice().iter()
+            .filter( |id| !markers.contains( id ) )
+            .map( |id| Dependency::from_id( self.pool, *id ) )
+            .collect()
+    }
+
+    pub fn dependencies ( &self, kind: DependencyKind ) -> Vec<Dependency> {
+        self.lookup_deps( kind.key(), 0 )
+    }
+
+    pub fn provides ( &self ) -> Vec<Dependency> {
+        self.dependencies( DependencyKind::Provides )
+    }
+
+    /// All requires, including the ones needed before installation, see prerequires
+    pub fn requires ( &self ) -> Vec<Dependency> {
+        self.dependencies( DependencyKind::Requires )
+    }
+
+    /// The requires without the prerequires, a negative marker selects the entries before it
+    pub fn plain_requires ( &self ) -> Vec<Dependency> {
+        self.lookup_deps( DependencyKind::Requires.key(), -( raw::solv_knownid_SOLVABLE_PREREQMARKER as Id ) )
+    }
+
+    /// The requires that need to be installed before this package (rpm's Requires(pre)),
+    /// a positive marker selects the entries after it
+    pub fn prerequires ( &self ) -> Vec<Dependency> {
+        self.lookup_deps( DependencyKind::Requires.key(), raw::solv_knownid_SOLVABLE_PREREQMARKER as Id )
+    }
+
+    pub fn conflicts ( &self ) -> Vec<Dependency> {
+        self.dependencies( DependencyKind::Conflicts )
+    }
+
+    pub fn obsoletes ( &self ) -> Vec<Dependency> {
+        self.dependencies( DependencyKind::Obsoletes )
+    }
+
+    pub fn recommends ( &self ) -> Vec<Dependency> {
+        self.dependencies( DependencyKind::Recommends )
+    }
+
+    pub fn suggests ( &self ) -> Vec<Dependency> {
+        self.dependencies( DependencyKind::Suggests )
+    }
+
+    pub fn supplements ( &self ) -> Vec<Dependency> {
+        self.dependencies( DependencyKind::Supplements )
+    }
+
+    pub fn enhances ( &self ) -> Vec<Dependency> {
+        self.dependencies( DependencyKind::Enhances )
+    }
+}
+
+impl fmt::Display for Solvable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = unsafe { Pool::str_from_ptr( raw::pool_solvable2str( self.pool.pool, self.as_ptr() ) ) };
+        f.write_str( &s.unwrap_or_default() )
+    }
+}
+
+impl fmt::Debug for Solvable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Solvable").field( "id", &self.id ).field( "name", &self.to_string() ).finish()
+    }
+}
@@ -0,0 +1,355 @@
+use solv_sys as raw;
+use std::ffi::CString;
+use std::io;
+use std::os::raw::c_int;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::error::PoolError;
+use super::pool::{Id, Pool};
+use super::queue::Queue;
+use super::transaction::Transaction;
+
+/// What a job should do with the solvables it selects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobAction {
+    Install,
+    Erase,
+    Update,
+    DistUpgrade,
+    /// keep the selected solvables in their current state, installed or not
+    Lock,
+    /// prefer the selected solvables if there is a choice
+    Favor,
+    /// avoid the selected solvables if there is a choice
+    Disfavor,
+    /// check that the dependencies of the selected installed solvables are fulfilled
+    Verify,
+}
+
+impl JobAction {
+    fn how ( &self ) -> u32 {
+        match self {
+            JobAction::Install => raw::SOLVER_INSTALL,
+            JobAction::Erase => raw::SOLVER_ERASE,
+            JobAction::Update => raw::SOLVER_UPDATE,
+            JobAction::DistUpgrade => raw::SOLVER_DISTUPGRADE,
+            JobAction::Lock => raw::SOLVER_LOCK,
+            JobAction::Favor => raw::SOLVER_FAVOR,
+            JobAction::Disfavor => raw::SOLVER_DISFAVOR,
+            JobAction::Verify => raw::SOLVER_VERIFY,
+        }
+    }
+}
+
+/// The solvables a job applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobTarget {
+    Solvable(Id),
+    /// one of the given solvables, the solver picks the best one
+    OneOf(Vec<Id>),
+    /// all solvables with the given name
+    Name(String),
+    /// all solvables providing the given capability, like "foo" or "foo >= 1.0"
+    Provides(String),
+    /// the how and what pairs of a libsolv selection, see Pool::select
+    Selection(Vec<(Id, Id)>),
+    /// all solvables of the repository with the given id
+    Repository(Id),
+    /// every solvable, e.g. for a full update or distupgrade
+    All,
+}
+
+/// One request for the solver, like installing a package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub action: JobAction,
+    pub target: JobTarget,
+    /// the job may be ignored if it can not be fulfilled, instead of causing a problem
+    pub weak: bool,
+    /// when erasing, also erase the dependencies that are no longer needed
+    pub clean_deps: bool,
+}
+
+impl Job {
+    pub fn new ( action: JobAction, target: JobTarget ) -> Self {
+        Self { action, target, weak: false, clean_deps: false }
+    }
+
+    /// Adds the how and what pairs of this job to a libsolv job queue
+    fn push_to ( &self, pool: &Pool, queue: &mut Queue ) -> Result<(), PoolError> {
+        let str2id = |s: &str| pool.str2id( s ).map_err( |_| PoolError::Io( io::Error::from( io::ErrorKind::InvalidInput ) ) );
+        let mut flags = self.action.how();
+        if self.weak {
+            flags |= raw::SOLVER_WEAK;
+        }
+        if self.clean_deps {
+            flags |= raw::SOLVER_CLEANDEPS;
+        }
+        let (select, what) = match &self.target {
+            JobTarget::Solvable(id) => ( raw::SOLVER_SOLVABLE, *id ),
+            JobTarget::OneOf(ids) => {
+                let mut q = Queue::new();
+                ids.iter().for_each( |id| q.push( *id ) );
+                ( raw::SOLVER_SOLVABLE_ONE_OF, unsafe { raw::pool_queuetowhatprovides( pool.pool, q.as_mut_ptr() ) } )
+            },
+            JobTarget::Name(name) => ( raw::SOLVER_SOLVABLE_NAME, str2id( name )? ),
+            JobTarget::Provides(capability) => ( raw::SOLVER_SOLVABLE_PROVIDES, pool.str2dep( capability )? ),
+            JobTarget::Selection(selection) => {
+                for (how, what) in selection.iter() {
+                    queue.push( *how | flags as Id );
+                    queue.push( *what );
+                }
+                return Ok(());
+            },
+            JobTarget::Repository(id) => ( raw::SOLVER_SOLVABLE_REPO, *id ),
+            JobTarget::All => ( raw::SOLVER_SOLVABLE_ALL, 0 ),
+        };
+        queue.push( ( flags | select ) as Id );
+        queue.push( what );
+        Ok(())
+    }
+}
+
+/// Flags changing the policies of the solver, see libsolv's SOLVER_FLAG_*
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SolverFlag {
+    AllowDowngrade,
+    AllowArchChange,
+    AllowVendorChange,
+    AllowNameChange,
+    AllowUninstall,
+    /// do not install recommended packages, like zypper's --no-recommends
+    IgnoreRecommended,
+    SplitProvides,
+    FocusInstalled,
+    FocusBest,
+    DupAllowDowngrade,
+    DupAllowArchChange,
+    DupAllowVendorChange,
+    DupAllowNameChange,
+}
+
+impl SolverFlag {
+    fn raw_flag ( &self ) -> c_int {
+        let flag = match self {
+            SolverFlag::AllowDowngrade => raw::SOLVER_FLAG_ALLOW_DOWNGRADE,
+            SolverFlag::AllowArchChange => raw::SOLVER_FLAG_ALLOW_ARCHCHANGE,
+            SolverFlag::AllowVendorChange => raw::SOLVER_FLAG_ALLOW_VENDORCHANGE,
+            SolverFlag::AllowNameChange => raw::SOLVER_FLAG_ALLOW_NAMECHANGE,
+            SolverFlag::AllowUninstall => raw::SOLVER_FLAG_ALLOW_UNINSTALL,
+            SolverFlag::IgnoreRecommended => raw::SOLVER_FLAG_IGNORE_RECOMMENDED,
+            SolverFlag::SplitProvides => raw::SOLVER_FLAG_SPLITPROVIDES,
+            SolverFlag::FocusInstalled => raw::SOLVER_FLAG_FOCUS_INSTALLED,
+            SolverFlag::FocusBest => raw::SOLVER_FLAG_FOCUS_BEST,
+            SolverFlag::DupAllowDowngrade => raw::SOLVER_FLAG_DUP_ALLOW_DOWNGRADE,
+            SolverFlag::DupAllowArchChange => raw::SOLVER_FLAG_DUP_ALLOW_ARCHCHANGE,
+            SolverFlag::DupAllowVendorChange => raw::SOLVER_FLAG_DUP_ALLOW_VENDORCHANGE,
+            SolverFlag::DupAllowNameChange => raw::SOLVER_FLAG_DUP_ALLOW_NAMECHANGE,
+        };
+        flag as c_int
+    }
+}
+
+/// What a solution proposes to resolve a problem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolutionAction {
+    /// remove the job with this index from the job list
+    RemoveJob(usize),
+    /// remove a job the pool added by itself
+    RemovePoolJob,
+    /// install or keep the solvable although it violates a policy, like a
+    /// lower repository priority or an inferior architecture
+    Allow(Id),
+    /// erase the installed solvable
+    Erase(Id),
+    /// replace the installed solvable with another one
+    Replace {
+        from: Id,
+        to: Id
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolutionElement {
+    pub action: SolutionAction,
+    pub description: String,
+}
+
+/// One way to resolve a problem, all of its elements have to be applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    pub elements: Vec<SolutionElement>,
+}
+
+/// A conflict the solver could not resolve on its own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub description: String,
+    pub solutions: Vec<Solution>,
+}
+
+/// Resolves the dependencies of a list of jobs against the solvables in a Pool
+pub struct Solver<'a> {
+    pool: &'a Pool,
+    solver: *mut raw::Solver,
+    flags: Vec<(SolverFlag, bool)>,
+    /// the index of the Job each how/what pair in the job queue came from, a job with a
+    /// selection target adds several pairs. Empty if the pairs were not made from Jobs.
+    job_of_pair: Vec<usize>,
+}
+
+impl<'a> Solver<'a> {
+
+    pub fn new ( pool: &'a Pool ) -> Self {
+        Solver { pool, solver: std::ptr::null_mut(), flags: Vec::new(), job_of_pair: Vec::new() }
+    }
+
+    /// Takes ownership of a solver libsolv created, e.g. when reading a testcase
+    pub( crate ) fn new_from_ptr ( pool: &'a Pool, solver: *mut raw::Solver ) -> Self {
+        Solver { pool, solver, flags: Vec::new(), job_of_pair: Vec::new() }
+    }
+
+    pub fn pool ( &self ) -> &'a Pool {
+        self.pool
+    }
+
+    pub( crate ) fn as_ptr ( &self ) -> *mut raw::Solver {
+        self.solver
+    }
+
+    pub fn set_flag ( &mut self, flag: SolverFlag, value: bool ) {
+        self.flags.retain( |(f, _)| *f != flag );
+        self.flags.push( (flag, value) );
+    }
+
+    /// Solves the jobs, returns the problems that need to be resolved by the user,
+    /// no problems means the jobs could be fulfilled.
+    pub fn solve ( &mut self, jobs: &[Job] ) -> Result<Vec<Problem>, PoolError> {
+        // repositories might have been added since the last run. The index is created before
+        // the jobs are added, it would drop the whatprovides lists of the jobs otherwise.
+        self.pool.ensure_whatprovides();
+        let mut queue = Queue::new();
+        let mut job_of_pair = Vec::new();
+        for (idx, job) in jobs.iter().enumerate() {
+            job.push_to( self.pool, &mut queue )?;
+            job_of_pair.resize( queue.as_slice().len() / 2, idx );
+        }
+        self.job_of_pair = job_of_pair;
+
+        // the solver is sized for the solvables in the pool, so it is created again as well
+        self.free_solver();
+        unsafe {
+            self.solver = raw::solver_create( self.pool.pool );
+            for (flag, value) in self.flags.iter() {
+                raw::solver_set_flag( self.solver, flag.raw_flag(), *value as c_int );
+            }
+        }
+        Ok( self.run( &mut queue ) )
+    }
+
+    /// Solves a raw job queue with the already created solver
+    pub( crate ) fn run ( &mut self, jobs: &mut Queue ) -> Vec<Problem> {
+        if unsafe { raw::solver_solve( self.solver, jobs.as_mut_ptr() ) } == 0 {
+            return Vec::new();
+        }
+        self.problems()
+    }
+
+    /// The changes the last successful solve decided on, None if solve was not called yet
+    pub fn transaction ( &self ) -> Option<Transaction<'a>> {
+        if self.solver.is_null() {
+            return None;
+        }
+        Some( Transaction::new_from_ptr( self.pool, unsafe { raw::solver_create_transaction( self.solver ) } ) )
+    }
+
+    /// Writes a libsolv testcase of the last solve into a directory, it contains the
+    /// repositories, the installed system, the jobs, the flags and the result, so the
+    /// solve can be reproduced with Testcase::read or libsolv's testsolv.
+    pub fn write_testcase<P: AsRef<Path>> ( &self, dir: P ) -> Result<(), PoolError> {
+        if self.solver.is_null() {
+            return Err( PoolError::Libsolv( "Nothing was solved yet".to_owned() ) );
+        }
+        let c_dir = CString::new( dir.as_ref().as_os_str().as_bytes() )
+            .map_err( |_| PoolError::Io( io::Error::from( io::ErrorKind::InvalidInput ) ) )?;
+        let flags = raw::TESTCASE_RESULT_TRANSACTION | raw::TESTCASE_RESULT_PROBLEMS;
+        unsafe {
+            if raw::testcase_write( self.solver, c_dir.as_ptr(), flags as c_int, std::ptr::null(), std::ptr::null() ) == 0 {
+                return Err( PoolError::Libsolv( self.pool.error_string() ) );
+            }
+        }
+        Ok(())
+    }
+
+    fn problems ( &self ) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        unsafe {
+            let mut problem = 0;
+            loop {
+                problem = raw::solver_next_problem( self.solver, problem );
+                if problem == 0 {
+                    break;
+                }
+                let mut solutions = Vec::new();
+                let mut solution = 0;
+                loop {
+                    solution = raw::solver_next_solution( self.solver, problem, solution );
+                    if solution == 0 {
+                        break;
+                    }
+                    solutions.push( Solution { elements: self.solution_elements( problem, solution ) } );
+                }
+                problems.push( Problem {
+                    description: Pool::str_from_ptr( raw::solver_problem2str( self.solver, problem ) ).unwrap_or_default(),
+                    solutions
+                });
+            }
+        }
+        problems
+    }
+
+    unsafe fn solution_elements ( &self, problem: Id, solution: Id ) -> Vec<SolutionElement> {
+        let mut elements = Vec::new();
+        let mut element = 0;
+        loop {
+            let (mut p, mut rp): (Id, Id) = (0, 0);
+            element = raw::solver_next_solutionelement( self.solver, problem, solution, element, &mut p, &mut rp );
+            if element == 0 {
+                break;
+            }
+            let action = match p {
+                p if p == raw::SOLVER_SOLUTION_JOB as Id => SolutionAction::RemoveJob( self.job_index( rp ) ),
+                p if p == raw::SOLVER_SOLUTION_POOLJOB as Id => SolutionAction::RemovePoolJob,
+                p if p > 0 && rp == 0 => SolutionAction::Erase( p ),
+                p if p > 0 => SolutionAction::Replace { from: p, to: rp },
+                _ => SolutionAction::Allow( rp ),
+            };
+            elements.push( SolutionElement {
+                action,
+                description: Pool::str_from_ptr( raw::solver_solutionelement2str( self.solver, p, rp ) ).unwrap_or_default()
+            });
+        }
+        elements
+    }
+
+    /// The index of the job a position in the job queue belongs to, rp of a solution
+    /// element points to the what part of a how/what pair
+    fn job_index ( &self, rp: Id ) -> usize {
+        let pair = ( ( rp - 1 ) / 2 ) as usize;
+        self.job_of_pair.get( pair ).copied().unwrap_or( pair )
+    }
+
+    fn free_solver ( &mut self ) {
+        if !self.solver.is_null() {
+            unsafe { raw::solver_free( self.solver ); }
+            self.solver = std::ptr::null_mut();
+        }
+    }
+}
+
+impl Drop for Solver<'_> {
+    fn drop(&mut self) {
+        self.free_solver();
+    }
+}
@@ -0,0 +1,88 @@
+use solv_sys as raw;
+use std::ffi::CString;
+use std::io;
+use std::os::raw::{c_char, c_int};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::error::PoolError;
+use super::pool::Pool;
+use super::queue::Queue;
+use super::solver::{Problem, Solver};
+
+/// A libsolv testcase read back into a Pool, see Solver::write_testcase. Solving it
+/// reproduces the original solve, the result can be compared to the recorded one.
+pub struct Testcase<'a> {
+    solver: Solver<'a>,
+    jobs: Queue,
+    expected: Option<String>,
+    result_flags: c_int,
+}
+
+/// Turns a string libsolv allocated into a String and frees it
+unsafe fn take_solv_string ( s: *mut c_char ) -> Option<String> {
+    let res = Pool::str_from_ptr( s );
+    if !s.is_null() {
+        raw::solv_free( s as *mut std::ffi::c_void );
+    }
+    res
+}
+
+impl<'a> Testcase<'a> {
+
+    /// Reads the testcase file, usually testcase.t in the directory written by
+    /// Solver::write_testcase. The repositories and the installed system are added
+    /// to the pool, which should be empty.
+    pub fn read<P: AsRef<Path>> ( pool: &'a Pool, file_path: P ) -> Result<Self, PoolError> {
+        let c_path = CString::new( file_path.as_ref().as_os_str().as_bytes() )
+            .map_err( |_| PoolError::Io( io::Error::from( io::ErrorKind::InvalidInput ) ) )?;
+        let mut jobs = Queue::new();
+        let mut result: *mut c_char = std::ptr::null_mut();
+        let mut result_flags: c_int = 0;
+        unsafe {
+            let solver = raw::testcase_read( pool.pool, std::ptr::null_mut(), c_path.as_ptr(), jobs.as_mut_ptr(), &mut result, &mut result_flags );
+            let expected = take_solv_string( result );
+            if solver.is_null() {
+                return Err( PoolError::Libsolv( pool.error_string() ) );
+            }
+            // libsolv created the provides index before it parsed the jobs, which refer to it
+            pool.whatprovides_dirty.set( false );
+            Ok( Testcase { solver: Solver::new_from_ptr( pool, solver ), jobs, expected, result_flags } )
+        }
+    }
+
+    /// Solves the jobs of the testcase with the flags that were recorded
+    pub fn solve ( &mut self ) -> Vec<Problem> {
+        self.solver.run( &mut self.jobs )
+    }
+
+    /// The solver of the testcase, e.g. to get the transaction after solve
+    pub fn solver ( &self ) -> &Solver<'a> {
+        &self.solver
+    }
+
+    /// The result that was recorded in the testcase, None if it has no result section
+    pub fn expected_result ( &self ) -> Option<&str> {
+        self.expected.as_deref()
+    }
+
+    /// The result of the last solve in the testcase format
+    pub fn result ( &self ) -> String {
+        unsafe { take_solv_string( raw::testcase_solverresult( self.solver.as_ptr(), self.result_flags ) ).unwrap_or_default() }
+    }
+
+    /// Compares the result of the last solve with the recorded one, returns the
+    /// differences if they do not match. A testcase without a result always matches.
+    pub fn check_result ( &self ) -> Result<(), String> {
+        let Some(expected) = self.expected.as_deref() else {
+            return Ok(());
+        };
+        let c_expected = CString::new( expected ).unwrap_or_default();
+        let c_result = CString::new( self.result() ).unwrap_or_default();
+        let diff = unsafe { take_solv_string( raw::testcase_resultdiff( c_expected.as_ptr(), c_result.as_ptr() ) ) };
+        match diff {
+            Some(diff) => Err( diff ),
+            None => Ok(())
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use solv_sys as raw;
+use std::os::raw::c_int;
+
+use super::pool::{Id, Pool};
+use super::queue::Queue;
+use super::solvable::Solvable;
+
+/// What happens to a solvable when the transaction is committed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    Install,
+    Erase,
+    /// a newer version replaces the installed one
+    Upgrade,
+    /// an older version replaces the installed one
+    Downgrade,
+    /// the same version is installed again, also used for arch and vendor changes
+    Reinstall,
+    /// a package with a different name replaces the installed packages it obsoletes
+    Obsolete,
+}
+
+impl StepKind {
+    fn from_type ( step_type: Id ) -> Option<Self> {
+        match step_type as u32 {
+            raw::SOLVER_TRANSACTION_INSTALL | raw::SOLVER_TRANSACTION_MULTIINSTALL => Some( StepKind::Install ),
+            raw::SOLVER_TRANSACTION_ERASE => Some( StepKind::Erase ),
+            raw::SOLVER_TRANSACTION_UPGRADE => Some( StepKind::Upgrade ),
+            raw::SOLVER_TRANSACTION_DOWNGRADE => Some( StepKind::Downgrade ),
+            raw::SOLVER_TRANSACTION_REINSTALL | raw::SOLVER_TRANSACTION_MULTIREINSTALL
+                | raw::SOLVER_TRANSACTION_CHANGE => Some( StepKind::Reinstall ),
+            raw::SOLVER_TRANSACTION_OBSOLETES => Some( StepKind::Obsolete ),
+            _ => None
+        }
+    }
+}
+
+/// One step of a transaction, the solvable is the package that gets installed,
+/// or for Erase the installed package that gets removed.
+#[derive(Debug, Clone)]
+pub struct TransactionStep<'a> {
+    pub kind: StepKind,
+    pub solvable: Solvable<'a>,
+    /// the installed packages this step replaces, empty for Install and Erase
+    pub replaces: Vec<Solvable<'a>>,
+}
+
+/// The changes to the installed system the solver decided on, see Solver::transaction
+pub struct Transaction<'a> {
+    pool: &'a Pool,
+    trans: *mut raw::Transaction,
+}
+
+impl<'a> Transaction<'a> {
+
+    pub( crate ) fn new_from_ptr ( pool: &'a Pool, trans: *mut raw::Transaction ) -> Self {
+        Transaction { pool, trans }
+    }
+
+    /// Classify the steps from the point of view of the packages that get installed,
+    /// so replaced packages are part of the step that replaces them.
+    const MODE: u32 = raw::SOLVER_TRANSACTION_SHOW_ACTIVE | raw::SOLVER_TRANSACTION_SHOW_OBSOLETES
+        | raw::SOLVER_TRANSACTION_CHANGE_IS_REINSTALL;
+
+    fn step_ids ( &self ) -> &[Id] {
+        let steps = unsafe { &(*self.trans).steps };
+        if steps.count <= 0 || steps.elements.is_null() {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts( steps.elements, steps.count as usize ) }
+    }
+
+    fn step ( &self, id: Id ) -> Option<TransactionStep<'a>> {
+        let kind = StepKind::from_type( unsafe { raw::transaction_type( self.trans, id, Self::MODE as c_int ) } )?;
+        let replaces = match kind {
+            StepKind::Install | StepKind::Erase => Vec::new(),
+            _ => {
+                let mut q = Queue::new();
+                unsafe { raw::transaction_all_obs_pkgs( self.trans, id, q.as_mut_ptr() ); }
+                q.as_slice().iter().map( |p| Solvable::new( self.pool, *p ) ).collect()
+            }
+        };
+        Some( TransactionStep { kind, solvable: Solvable::new( self.pool, id ), replaces } )
+    }
+
+    /// All steps of the transaction, in commit order once order was called
+    pub fn steps ( &self ) -> Vec<TransactionStep<'a>> {
+        self.step_ids().iter().filter_map( |id| self.step( *id ) ).collect()
+    }
+
+    pub fn is_empty ( &self ) -> bool {
+        self.steps().is_empty()
+    }
+
+    /// Sorts the steps so that packages are installed after the packages they require,
+    /// which is the order they need to be handed to rpm in
+    pub fn order ( &mut self ) {
+        unsafe { raw::transaction_order( self.trans, 0 ); }
+    }
+
+    /// Number of bytes that need to be downloaded to commit the transaction
+    pub fn download_size ( &self ) -> u64 {
+        self.steps().iter()
+            .filter( |s| s.kind != StepKind::Erase )
+            .map( |s| s.solvable.download_size() )
+            .sum()
+    }
+
+    /// How much the size of the installed system changes in bytes, negative if it shrinks
+    pub fn install_size_change ( &self ) -> i64 {
+        // libsolv calculates in KiB
+        unsafe { raw::transaction_calc_installsizechange( self.trans ) as i64 * 1024 }
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        unsafe { raw::transaction_free( self.trans ); }
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use thiserror::Error;
+
+use crate::checksum::CheckSum;
+use crate::solv::file::SolvFile;
+
+#[derive(Error, Debug)]
+pub enum SusetagsError {
+    #[error("Failed to read susetags metadata - {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid content file - {0}")]
+    Invalid(String),
+}
+
+/// A file listed in the content file together with its checksum
+#[derive(Debug, Clone)]
+pub struct ContentFileEntry {
+    pub checksum: CheckSum,
+    pub file: String,
+}
+
+/// The content file in the root of a susetags (yast2) repository, it describes
+/// the directory layout and holds the checksums of all metadata files.
+#[derive(Debug, Clone)]
+pub struct ContentFile {
+    pub label: Option<String>,
+    /// the default vendor of all packages in the repository
+    pub vendor: Option<String>,
+    /// directory of the packages, relative to the repository root
+    pub data_dir: String,
+    /// directory of the package descriptions, relative to the repository root
+    pub descr_dir: String,
+    /// META entries, files in descr_dir
+    pub meta: Vec<ContentFileEntry>,
+    /// HASH entries, files relative to the repository root
+    pub hashes: Vec<ContentFileEntry>,
+    /// KEY entries, gpg keys relative to the repository root
+    pub keys: Vec<ContentFileEntry>,
+}
+
+impl Default for ContentFile {
+    fn default() -> Self {
+        Self {
+            label: None,
+            vendor: None,
+            data_dir: "suse".to_owned(),
+            descr_dir: "suse/setup/descr".to_owned(),
+            meta: Vec::new(),
+            hashes: Vec::new(),
+            keys: Vec::new(),
+        }
+    }
+}
+
+fn parse_entry( line: &str, value: &str ) -> Result<ContentFileEntry, SusetagsError> {
+    let mut parts = value.split_whitespace();
+    let (Some(kind), Some(sum), Some(file), None) = ( parts.next(), parts.next(), parts.next(), parts.next() ) else {
+        return Err( SusetagsError::Invalid( format!("Malformed line: {}", line) ) );
+    };
+    let checksum = CheckSum::from_type_str( kind, sum ).map_err( |e| SusetagsError::Invalid( e.to_string() ) )?;
+    Ok( ContentFileEntry { checksum, file: file.to_owned() } )
+}
+
+impl ContentFile {
+
+    pub fn parse( content: &str ) -> Result<Self, SusetagsError> {
+        let mut res = ContentFile::default();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once( char::is_whitespace ).unwrap_or( (line, "") );
+            let value = value.trim();
+            match key {
+                "LABEL" => res.label = Some( value.to_owned() ),
+                "VENDOR" => res.vendor = Some( value.to_owned() ),
+                "DATADIR" => res.data_dir = value.to_owned(),
+                "DESCRDIR" => res.descr_dir = value.to_owned(),
+                "META" => res.meta.push( parse_entry( line, value )? ),
+                "HASH" => res.hashes.push( parse_entry( line, value )? ),
+                "KEY" => res.keys.push( parse_entry( line, value )? ),
+                // everything else is product data, libsolv reads that itself
+                _ => {}
+            }
+        }
+        Ok( res )
+    }
+
+    pub fn read_from_file<P: AsRef<Path>>( file_path: P ) -> Result<Self, SusetagsError> {
+        Self::parse( &fs::read_to_string( file_path )? )
+    }
+
+    /// Finds the META entry of a file in the description directory, compressed
+    /// variants like packages.gz are found for packages as well. The name may be
+    /// compressed itself, like the foo.pat.gz entries of a patterns file.
+    pub fn find_meta( &self, name: &str ) -> Option<&ContentFileEntry> {
+        let name = strip_compression( name );
+        self.meta.iter().find( |m| strip_compression( &m.file ) == name )
+    }
+}
+
+/// Removes the suffix of a compressed file name, packages.gz becomes packages
+pub fn strip_compression( file: &str ) -> &str {
+    [".gz", ".xz", ".zst", ".bz2"].iter()
+        .find_map( |ext| file.strip_suffix( ext ) )
+        .unwrap_or( file )
+}
+
+/// Reads the pattern file names from the patterns file of a description directory,
+/// which might be compressed like patterns.gz
+pub fn read_patterns_list<P: AsRef<Path>>( file_path: P ) -> Result<Vec<String>, SusetagsError> {
+    let mut list = String::new();
+    SolvFile::open( file_path, "r" )?.read_to_string( &mut list )?;
+    Ok( list
+        .lines()
+        .map( str::trim )
+        .filter( |l| !l.is_empty() && !l.starts_with('#') )
+        .map( str::to_owned )
+        .collect() )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1: &str = "a9993e364706816aba3e25717850c26c9cd0d89d";
+    const SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn content() -> String {
+        format!( "\
+# a comment
+CONTENTSTYLE 11
+LABEL openSUSE Leap 15.5
+VENDOR openSUSE
+DATADIR suse
+DESCRDIR suse/setup/descr
+META SHA256 {sha256} packages.gz
+META SHA256 {sha256} packages.en.gz
+META SHA1 {sha1} base-15.5.pat.gz
+META SHA256 {sha256} patterns
+HASH SHA256 {sha256} license.tar.gz
+KEY SHA1 {sha1} gpg-pubkey-307e3d54-5aaa90a5.asc
+", sha256 = SHA256, sha1 = SHA1 )
+    }
+
+    #[test]
+    fn parses_the_content_file() {
+        let content = ContentFile::parse( &content() ).unwrap();
+        assert_eq!( content.label.as_deref(), Some( "openSUSE Leap 15.5" ) );
+        assert_eq!( content.vendor.as_deref(), Some( "openSUSE" ) );
+        assert_eq!( ( content.data_dir.as_str(), content.descr_dir.as_str() ), ( "suse", "suse/setup/descr" ) );
+        assert_eq!( content.meta.len(), 4 );
+        assert_eq!( content.hashes[0].file, "license.tar.gz" );
+        assert_eq!( content.keys[0].checksum.to_string(), format!( "sha1:{}", SHA1 ) );
+
+        // the defaults if the directories are not set
+        let content = ContentFile::parse( "LABEL minimal\n" ).unwrap();
+        assert_eq!( ( content.data_dir.as_str(), content.descr_dir.as_str() ), ( "suse", "suse/setup/descr" ) );
+    }
+
+    #[test]
+    fn finds_compressed_meta_files() {
+        let content = ContentFile::parse( &content() ).unwrap();
+        assert_eq!( content.find_meta( "packages" ).unwrap().file, "packages.gz" );
+        assert_eq!( content.find_meta( "packages.gz" ).unwrap().file, "packages.gz" );
+        assert_eq!( content.find_meta( "packages.en" ).unwrap().file, "packages.en.gz" );
+        assert_eq!( content.find_meta( "base-15.5.pat" ).unwrap().file, "base-15.5.pat.gz" );
+        assert_eq!( content.find_meta( "base-15.5.pat.xz" ).unwrap().file, "base-15.5.pat.gz" );
+        assert_eq!( content.find_meta( "patterns.gz" ).unwrap().file, "patterns" );
+        assert!( content.find_meta( "packages.de" ).is_none() );
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        assert!( matches!( ContentFile::parse( "META SHA256 packages.gz\n" ), Err( SusetagsError::Invalid( _ ) ) ) );
+        assert!( matches!( ContentFile::parse( &format!( "META SHA256 {} packages.gz extra\n", SHA256 ) ), Err( SusetagsError::Invalid( _ ) ) ) );
+        assert!( matches!( ContentFile::parse( &format!( "META SHA256 {} packages.gz\n", SHA1 ) ), Err( SusetagsError::Invalid( _ ) ) ) );
+        assert!( matches!( ContentFile::parse( &format!( "HASH CRC32 {} license.tar.gz\n", SHA1 ) ), Err( SusetagsError::Invalid( _ ) ) ) );
+    }
+
+    #[test]
+    fn compression_suffixes() {
+        assert_eq!( strip_compression( "packages.gz" ), "packages" );
+        assert_eq!( strip_compression( "packages.zst" ), "packages" );
+        assert_eq!( strip_compression( "packages.en" ), "packages.en" );
+        assert_eq!( strip_compression( "packages" ), "packages" );
+    }
+}
@@ -0,0 +1,7 @@
+repo system 99 testtags system.repo
+repo available 99 testtags available.repo
+system x86_64 rpm system
+job install name foo
+result transaction,problems <inline>
+#>install foo-2.0-1.x86_64@available
+#>upgrade libfoo1-1.0-1.x86_64@system libfoo1-1.2-1.x86_64@available
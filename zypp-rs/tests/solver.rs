@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use zypp_rs::solv::pool::Pool;
+use zypp_rs::solv::query::{MatchMode, Query};
+use zypp_rs::solv::solver::{Job, JobAction, JobTarget, SolutionAction, Solver};
+use zypp_rs::solv::testcase::Testcase;
+
+fn fixture ( name: &str ) -> PathBuf {
+    PathBuf::from( env!("CARGO_MANIFEST_DIR") ).join( "tests/data/testcase" ).join( name ).join( "testcase.t" )
+}
+
+#[test]
+fn solutions_name_the_job_after_a_selection () {
+    let pool = Pool::new();
+    // only used to load the repositories and the installed system
+    let _testcase = Testcase::read( &pool, fixture( "install" ) ).unwrap();
+
+    // locks foo and libfoo1, a selection with one how/what pair for each name
+    let locks = pool.select( &Query::new( "*o*", MatchMode::Glob ) ).unwrap();
+    let JobTarget::Selection(pairs) = &locks else {
+        panic!( "Expected a selection, got {:?}", locks );
+    };
+    assert!( pairs.len() > 1, "{:?}", pairs );
+
+    let jobs = [
+        Job::new( JobAction::Lock, locks ),
+        Job::new( JobAction::Install, JobTarget::Provides( "foo = 2.0-1".to_owned() ) ),
+    ];
+    let mut solver = Solver::new( &pool );
+    let problems = solver.solve( &jobs ).unwrap();
+    assert_eq!( problems.len(), 1 );
+
+    let mut removed: Vec<usize> = problems[0].solutions.iter()
+        .flat_map( |s| s.elements.iter() )
+        .filter_map( |e| match e.action {
+            SolutionAction::RemoveJob(idx) => Some( idx ),
+            _ => None
+        })
+        .collect();
+    removed.sort();
+    removed.dedup();
+    assert_eq!( removed, vec![ 0, 1 ] );
+}
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use zypp_rs::solv::pool::Pool;
+use zypp_rs::solv::testcase::Testcase;
+
+fn fixture ( name: &str ) -> PathBuf {
+    PathBuf::from( env!("CARGO_MANIFEST_DIR") ).join( "tests/data/testcase" ).join( name ).join( "testcase.t" )
+}
+
+#[test]
+fn solves_to_the_recorded_result () {
+    let pool = Pool::new();
+    let mut testcase = Testcase::read( &pool, fixture( "install" ) ).unwrap();
+    assert!( testcase.expected_result().is_some() );
+
+    let problems = testcase.solve();
+    assert!( problems.is_empty() );
+    assert_eq!( testcase.check_result(), Ok(()) );
+}
+
+#[test]
+fn written_testcase_reads_back () {
+    let pool = Pool::new();
+    let mut testcase = Testcase::read( &pool, fixture( "install" ) ).unwrap();
+    testcase.solve();
+
+    let dir = tempfile::tempdir().unwrap();
+    let written = dir.path().join( "testcase" );
+    testcase.solver().write_testcase( &written ).unwrap();
+
+    let written_pool = Pool::new();
+    let mut written_testcase = Testcase::read( &written_pool, written.join( "testcase.t" ) ).unwrap();
+    assert_eq!( written_testcase.expected_result(), Some( testcase.result().as_str() ) );
+    written_testcase.solve();
+    assert_eq!( written_testcase.check_result(), Ok(()) );
+}